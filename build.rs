@@ -0,0 +1,32 @@
+use std::path::Path;
+use std::{env, fs};
+
+/// Embeds every font in `assets/fonts` into the binary, see `src/fonts.rs`.
+fn main() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/fonts");
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    let mut fonts: Vec<_> = fs::read_dir(&dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| {
+                    matches!(
+                        extension.to_ascii_lowercase().as_str(),
+                        "ttf" | "otf" | "ttc"
+                    )
+                })
+        })
+        .collect();
+    fonts.sort();
+
+    let entries: String = fonts
+        .iter()
+        .map(|path| format!("    include_bytes!({:?}),\n", path.display().to_string()))
+        .collect();
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("bundled_fonts.rs");
+    fs::write(out, format!("&[\n{entries}]\n")).unwrap();
+}
@@ -0,0 +1,25 @@
+CREATE TABLE captures (
+    id INTEGER PRIMARY KEY,
+    timestamp INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    bundle_id TEXT,
+    app_name TEXT,
+    window_title TEXT,
+    document TEXT,
+    capture TEXT NOT NULL
+);
+CREATE INDEX captures_timestamp ON captures (timestamp);
+CREATE INDEX captures_bundle_id ON captures (bundle_id);
+
+CREATE VIRTUAL TABLE captures_fts USING fts5(
+    text, app_name, window_title, document,
+    content = 'captures', content_rowid = 'id'
+);
+CREATE TRIGGER captures_insert AFTER INSERT ON captures BEGIN
+    INSERT INTO captures_fts (rowid, text, app_name, window_title, document)
+    VALUES (new.id, new.text, new.app_name, new.window_title, new.document);
+END;
+CREATE TRIGGER captures_delete AFTER DELETE ON captures BEGIN
+    INSERT INTO captures_fts (captures_fts, rowid, text, app_name, window_title, document)
+    VALUES ('delete', old.id, old.text, old.app_name, old.window_title, old.document);
+END;
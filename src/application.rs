@@ -1,39 +1,196 @@
 use std::sync::{Arc, mpsc};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::Context;
-use wgpu::InstanceDescriptor;
 use winit::application::ApplicationHandler;
-use winit::dpi::{LogicalSize, PhysicalSize};
-use winit::event::WindowEvent;
-use winit::event_loop::{ActiveEventLoop, EventLoop};
+use winit::dpi::{LogicalSize, PhysicalPosition, PhysicalSize};
+use winit::event::{ElementState, KeyEvent, MouseScrollDelta, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
+use winit::keyboard::{Key, ModifiersState, NamedKey};
 use winit::platform::macos::WindowAttributesExtMacOS;
-use winit::window::{Window, WindowAttributes, WindowLevel};
+use winit::window::{Window, WindowAttributes, WindowId, WindowLevel};
 
+use crate::capture::Capture;
+use crate::card::{Card, Command};
+use crate::clipboard::{SystemKeyboard, SystemPasteboard};
+use crate::config::Config;
 use crate::controller::Controller;
 use crate::event::Event;
+use crate::fonts::Fonts;
+use crate::history::History;
+use crate::layout::{Extent, SizeLimits};
+use crate::lifecycle::{Lifecycle, SystemClock, Timing};
+use crate::overlay::Overlay;
+use crate::pins::{Pin, Pins};
+use crate::provider::{self, Registry};
+use crate::render::{GpuRenderer, Renderer, SoftwareRenderer};
+use crate::stabilizer::Stabilizer;
+use crate::theme::{Appearance, Theme};
+use crate::{highlight, sink};
 
-const WINDOW_SIZE: (u32, u32) = (320, 36);
+/// How long pinned windows have to stay put before their positions are saved,
+/// so dragging one doesn't write the pins on every step.
+const SAVE_PINS_DELAY: Duration = Duration::from_millis(500);
 
-const FONT_SIZE: f32 = 32.0;
-const LINE_HEIGHT: f32 = 36.0;
+/// How often the history is pruned while captures are recorded.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
-const TEXT_COLOR: glyphon::Color = glyphon::Color::rgb(0, 0, 0);
-
-#[derive(Default)]
 pub struct App {
     state: Option<State>,
+    pinned: Vec<Pinned>,
+    /// When a pinned window was last moved, if its position hasn't been saved
+    /// since.
+    pins_moved: Option<Instant>,
     event_rx: Option<mpsc::Receiver<Event>>,
     controller: Option<Controller>,
+    history: Option<History>,
+    /// When the history was last pruned.
+    pruned: Instant,
+    /// Delivers captures to the configured sinks.
+    sink_tx: Option<mpsc::Sender<Capture>>,
+    /// Asks the context providers for cards about the selection shown.
+    provider_tx: Option<mpsc::Sender<(u64, Capture)>>,
+    /// Counts the selections shown, so cards for earlier ones are dropped.
+    generation: u64,
+    fonts: Fonts,
+    config: Config,
 }
 
 impl App {
+    pub fn new(config: Config) -> Self {
+        Self {
+            state: None,
+            pinned: Vec::new(),
+            pins_moved: None,
+            event_rx: None,
+            controller: None,
+            history: None,
+            pruned: Instant::now(),
+            sink_tx: None,
+            provider_tx: None,
+            generation: 0,
+            fonts: Fonts::new(config.fonts.clone()),
+            config,
+        }
+    }
+
     pub fn run(&mut self) -> anyhow::Result<()> {
         let event_loop = EventLoop::new().unwrap();
         event_loop.run_app(self)?;
+        if self.pins_moved.is_some() {
+            self.save_pins();
+        }
+        Ok(())
+    }
+
+    fn state_mut(&mut self, window_id: WindowId) -> Option<&mut State> {
+        match &mut self.state {
+            Some(state) if state.window.id() == window_id => Some(state),
+            _ => self
+                .pinned
+                .iter_mut()
+                .map(|pinned| &mut pinned.state)
+                .find(|state| state.window.id() == window_id),
+        }
+    }
+
+    fn run_command(
+        &mut self,
+        event_loop: &dyn ActiveEventLoop,
+        window_id: WindowId,
+        command: Command,
+    ) {
+        match command {
+            Command::Copy(text) => SystemPasteboard.set_string(&text),
+            Command::Open(target) => {
+                if let Err(err) = std::process::Command::new("open")
+                    .arg("--")
+                    .arg(&target)
+                    .spawn()
+                {
+                    eprintln!("failed to open {target}: {err}");
+                }
+            }
+            Command::Pin => self.pin_current(event_loop),
+            Command::Dismiss => self.dismiss(window_id),
+        }
+    }
+
+    /// Pins the card shown in the overlay where it is, and dismisses the
+    /// overlay.
+    fn pin_current(&mut self, event_loop: &dyn ActiveEventLoop) {
+        let Some(state) = &mut self.state else {
+            return;
+        };
+        let card = state.overlay.card();
+        let position = state
+            .window
+            .outer_position()
+            .ok()
+            .map(|position| (position.x, position.y));
+        let pin = Pin::new(card, position);
+        state.lifecycle.dismiss();
+
+        if let Err(err) = self.pin(event_loop, pin) {
+            eprintln!("failed to pin: {err:#}");
+        }
+        self.save_pins();
+    }
+
+    /// Opens a window for `pin`.
+    fn pin(&mut self, event_loop: &dyn ActiveEventLoop, pin: Pin) -> anyhow::Result<()> {
+        let window = create_window(event_loop, &self.config.size)?;
+        if let Some((x, y)) = pin.position {
+            window.set_outer_position(PhysicalPosition::new(x, y).into());
+        }
+        let appearance = window.theme().map(Appearance::from).unwrap_or_default();
+        let theme = self.config.theme(appearance);
+
+        let mut state = pollster::block_on(State::new(
+            window,
+            self.fonts.font_system(),
+            self.config.size,
+            self.config.timing,
+            theme,
+        ))?;
+        state.lifecycle.pin();
+        state.set_card(pin.card());
+        state.lifecycle.show();
+
+        self.pinned.push(Pinned { pin, state });
         Ok(())
     }
+
+    /// Fades out the overlay, or closes a pinned window.
+    fn dismiss(&mut self, window_id: WindowId) {
+        if let Some(state) = &mut self.state
+            && state.window.id() == window_id
+        {
+            state.lifecycle.dismiss();
+            return;
+        }
+        self.pinned
+            .retain(|pinned| pinned.state.window.id() != window_id);
+        self.save_pins();
+    }
+
+    fn save_pins(&mut self) {
+        self.pins_moved = None;
+        let pins = Pins {
+            pins: self
+                .pinned
+                .iter()
+                .map(|pinned| pinned.pin.clone())
+                .collect(),
+        };
+        let saved = Pins::path()
+            .context("no data directory")
+            .and_then(|path| pins.save(&path));
+        if let Err(err) = saved {
+            eprintln!("failed to save pins: {err:#}");
+        }
+    }
 }
 
 impl ApplicationHandler for App {
@@ -44,232 +201,497 @@ impl ApplicationHandler for App {
     }
 
     fn can_create_surfaces(&mut self, event_loop: &dyn winit::event_loop::ActiveEventLoop) {
-        let window = create_window(event_loop).unwrap();
-        self.state = Some(pollster::block_on(State::new(window)));
+        let window = create_window(event_loop, &self.config.size).unwrap();
+        let appearance = window.theme().map(Appearance::from).unwrap_or_default();
+        let theme = self.config.theme(appearance);
+        self.state = Some(
+            pollster::block_on(State::new(
+                window,
+                self.fonts.font_system(),
+                self.config.size,
+                self.config.timing,
+                theme,
+            ))
+            .unwrap(),
+        );
 
         let (event_tx, event_rx) = mpsc::channel();
         self.event_rx = Some(event_rx);
 
         // self.controller = Some(Controller::new(event_tx));
 
+        let proxy = event_loop.create_proxy();
+        self.provider_tx = Some(provider::spawn(
+            Registry::new(&self.config.providers),
+            event_tx.clone(),
+            proxy.clone(),
+        ));
+
+        let stabilizer = Stabilizer::new(SystemClock, self.config.stabilization);
+        let rules = self.config.rules.clone();
+        let normalization = self.config.normalization;
+        let redaction = self.config.redaction.clone();
+        let fallback = self.config.copy_fallback;
+        let keyboard = SystemKeyboard::current();
         thread::spawn(move || {
-            let controller = Controller::new();
-            loop {
-                let focused_app = controller.focused_app().unwrap();
-                let focused_element = controller.focused_element(Some(&focused_app));
-
-                if let Ok(focused_element) = focused_element {
-                    let selected_text = controller.selected_text(&focused_element);
-                    let selected_text_bounds = controller.selected_text_bounds(&focused_element);
-                    // TODO
+            Controller::new(rules, normalization, redaction, fallback, keyboard)
+                .run(stabilizer, event_tx, proxy)
+        });
+
+        self.history = History::path()
+            .context("no data directory")
+            .and_then(|path| History::open(&path))
+            .inspect(|history| {
+                if let Err(err) = history.prune(&self.config.history) {
+                    eprintln!("failed to prune history: {err:#}");
                 }
-                thread::sleep(Duration::from_millis(1500));
+            })
+            .inspect_err(|err| eprintln!("failed to open history: {err:#}"))
+            .ok();
+
+        self.sink_tx = sink::spawn(
+            &self.config.sinks,
+            self.config.outbox,
+            self.config.rules.clone(),
+        );
+
+        let pins = Pins::path()
+            .map_or_else(|| Ok(Pins::default()), |path| Pins::load(&path))
+            .unwrap_or_else(|err| {
+                eprintln!("failed to restore pins: {err:#}");
+                Pins::default()
+            });
+        for pin in pins.pins {
+            if let Err(err) = self.pin(event_loop, pin) {
+                eprintln!("failed to restore pin: {err:#}");
+            }
+        }
+    }
+
+    fn proxy_wake_up(&mut self, _event_loop: &dyn ActiveEventLoop) {
+        let (Some(state), Some(event_rx)) = (&mut self.state, &self.event_rx) else {
+            return;
+        };
+
+        for event in event_rx.try_iter() {
+            let capture = match event {
+                Event::SelectionChanged(capture) | Event::SelectionExtended(capture) => capture,
+                Event::SelectionCleared => {
+                    if let Some(history) = &mut self.history {
+                        history.forget_last();
+                    }
+                    state.lifecycle.hide();
+                    continue;
+                }
+                Event::Cards { generation, cards } => {
+                    if generation == self.generation {
+                        state.add_cards(cards);
+                    }
+                    continue;
+                }
+            };
+            if let Some(history) = &mut self.history
+                && let Err(err) = history.record(&capture)
+            {
+                eprintln!("failed to record capture: {err:#}");
+            }
+            // extensions are marked, so sinks can replace the capture before
+            if let Some(sink_tx) = &self.sink_tx {
+                let _ = sink_tx.send(capture.clone());
             }
+            if self.config.rules.shows(&capture) {
+                self.generation += 1;
+                state.set_selection(&capture);
+                state.lifecycle.show();
+                if let Some(provider_tx) = &self.provider_tx {
+                    let _ = provider_tx.send((self.generation, capture));
+                }
+            }
+        }
+
+        if let Some(history) = &self.history
+            && self.pruned.elapsed() >= PRUNE_INTERVAL
+        {
+            self.pruned = Instant::now();
+            if let Err(err) = history.prune(&self.config.history) {
+                eprintln!("failed to prune history: {err:#}");
+            }
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &dyn ActiveEventLoop) {
+        let states = self
+            .state
+            .iter_mut()
+            .chain(self.pinned.iter_mut().map(|pinned| &mut pinned.state));
+        let deadline = states.filter_map(State::update).min();
+
+        if let Some(moved) = self.pins_moved
+            && Instant::now() >= moved + SAVE_PINS_DELAY
+        {
+            self.save_pins();
+        }
+        let deadline = deadline
+            .into_iter()
+            .chain(self.pins_moved.map(|moved| moved + SAVE_PINS_DELAY))
+            .min();
+        event_loop.set_control_flow(match deadline {
+            Some(deadline) => ControlFlow::WaitUntil(deadline),
+            None => ControlFlow::Wait,
         });
     }
 
     fn window_event(
         &mut self,
         event_loop: &dyn winit::event_loop::ActiveEventLoop,
-        _window_id: winit::window::WindowId,
+        window_id: winit::window::WindowId,
         event: winit::event::WindowEvent,
     ) {
-        let Some(state) = &mut self.state else {
+        if let Some(pinned) = self
+            .pinned
+            .iter_mut()
+            .find(|pinned| pinned.state.window.id() == window_id)
+        {
+            match event {
+                WindowEvent::Moved(position) => {
+                    pinned.pin.position = Some((position.x, position.y));
+                    self.pins_moved = Some(Instant::now());
+                    return;
+                }
+                WindowEvent::CloseRequested => {
+                    self.dismiss(window_id);
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        if let WindowEvent::ThemeChanged(theme) = event
+            && self.config.follows_appearance()
+        {
+            let theme = self.config.theme(theme.into());
+            if let Some(state) = self.state_mut(window_id) {
+                state.set_theme(theme);
+            }
+            return;
+        }
+
+        let Some(state) = self.state_mut(window_id) else {
             return;
         };
 
+        let mut command = None;
         match event {
             WindowEvent::SurfaceResized(size) => {
                 state.resize(size);
                 state.window.request_redraw();
             }
+            WindowEvent::ScaleFactorChanged {
+                scale_factor,
+                mut surface_size_writer,
+            } => {
+                // keep the logical size, so the overlay looks the same on
+                // every monitor
+                let size = state.extent.to_physical(scale_factor);
+                if let Err(err) = surface_size_writer.request_surface_size(size) {
+                    eprintln!("failed to resize for scale factor {scale_factor}: {err}");
+                }
+                state.resize(size);
+                state.window.request_redraw();
+            }
             WindowEvent::RedrawRequested => {
-                state.render();
+                if let Err(err) = state.render() {
+                    eprintln!("failed to render: {err:#}");
+                }
+            }
+            WindowEvent::PointerEntered { .. } => state.lifecycle.set_hovered(true),
+            WindowEvent::PointerMoved { position, .. } => state.pointer_moved(position),
+            WindowEvent::PointerLeft { .. } => {
+                state.lifecycle.set_hovered(false);
+                if state.overlay.pointer_left() {
+                    state.window.request_redraw();
+                }
             }
+            WindowEvent::PointerButton {
+                state: button_state,
+                primary: true,
+                position,
+                ..
+            } => {
+                state.pointer_moved(position);
+                command = state.pointer_button(button_state);
+            }
+            WindowEvent::MouseWheel { delta, .. } => state.scroll(delta),
+            WindowEvent::ModifiersChanged(modifiers) => state.modifiers = modifiers.state(),
+            WindowEvent::KeyboardInput { event, .. } => command = state.key(event),
             WindowEvent::CloseRequested => event_loop.exit(),
             _ => {}
         }
+
+        if let Some(command) = command {
+            self.run_command(event_loop, window_id, command);
+        }
     }
 }
 
+/// A pinned capture and the window it's shown in.
+struct Pinned {
+    pin: Pin,
+    state: State,
+}
+
+/// The inputs of a rendered frame, which is only redrawn when one changes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Frame {
+    revision: u64,
+    size: PhysicalSize<u32>,
+    scale: f64,
+    opacity: f32,
+}
+
 struct State {
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    surface: wgpu::Surface<'static>,
-    surface_config: wgpu::SurfaceConfiguration,
-
-    font_system: glyphon::FontSystem,
-    swash_cache: glyphon::SwashCache,
-    viewport: glyphon::Viewport,
-    atlas: glyphon::TextAtlas,
-    text_renderer: glyphon::TextRenderer,
-    text_buffer: glyphon::Buffer,
+    overlay: Overlay,
+    renderer: Box<dyn Renderer>,
+    lifecycle: Lifecycle,
+    modifiers: ModifiersState,
+    /// What's on screen, `None` if it needs drawing regardless.
+    rendered: Option<Frame>,
+    /// Logical size the overlay asked for.
+    extent: Extent,
+    /// Cards about the selection that can be cycled through, best first.
+    cards: Vec<Card>,
+    /// Index of the card in `cards` that's shown.
+    shown: usize,
 
     window: Arc<dyn Window>,
 }
 
 impl State {
-    async fn new(window: Arc<dyn Window>) -> Self {
-        let physical_size = window.surface_size();
-        let logical_size = physical_size.to_logical::<f64>(window.scale_factor());
-
-        // initialize wgpu
-        let instance = wgpu::Instance::new(&InstanceDescriptor::default());
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions::default())
-            .await
-            .unwrap();
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor::default())
-            .await
-            .unwrap();
-
-        // configure surface
-        let surface = instance
-            .create_surface(window.clone())
-            .expect("create surface");
-        let swapchain_format = wgpu::TextureFormat::Bgra8UnormSrgb;
-        let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: swapchain_format,
-            width: physical_size.width,
-            height: physical_size.height,
-            present_mode: wgpu::PresentMode::AutoVsync,
-            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
-            view_formats: vec![],
-            desired_maximum_frame_latency: 2,
+    async fn new(
+        window: Arc<dyn Window>,
+        font_system: glyphon::FontSystem,
+        size_limits: SizeLimits,
+        timing: Timing,
+        theme: Theme,
+    ) -> anyhow::Result<Self> {
+        let renderer: Box<dyn Renderer> = match GpuRenderer::new(window.clone()).await {
+            Ok(renderer) => Box::new(renderer),
+            Err(err) => {
+                eprintln!("falling back to software rendering: {err:#}");
+                Box::new(SoftwareRenderer::new(window.clone())?)
+            }
         };
-        surface.configure(&device, &surface_config);
-
-        // text renderer
-        let mut font_system = glyphon::FontSystem::new();
-        let swash_cache = glyphon::SwashCache::new();
-        let cache = glyphon::Cache::new(&device);
-        let viewport = glyphon::Viewport::new(&device, &cache);
-        let mut atlas = glyphon::TextAtlas::new(&device, &queue, &cache, swapchain_format);
-        let text_renderer = glyphon::TextRenderer::new(
-            &mut atlas,
-            &device,
-            wgpu::MultisampleState::default(),
-            None,
-        );
-        let mut text_buffer = glyphon::Buffer::new(
-            &mut font_system,
-            glyphon::Metrics::new(FONT_SIZE, LINE_HEIGHT),
-        );
-        text_buffer.set_size(
-            &mut font_system,
-            Some(logical_size.width as f32),
-            Some(logical_size.height as f32),
-        );
 
-        Self {
-            device,
-            queue,
-            surface,
-            surface_config,
-            font_system,
-            swash_cache,
-            viewport,
-            atlas,
-            text_renderer,
-            text_buffer,
+        window.set_blur(theme.blur);
+
+        Ok(Self {
+            overlay: Overlay::new(font_system, size_limits, theme),
+            renderer,
+            lifecycle: Lifecycle::new(SystemClock, timing),
+            modifiers: ModifiersState::default(),
+            rendered: None,
+            extent: Extent {
+                width: size_limits.min_width,
+                height: size_limits.min_height,
+            },
+            cards: Vec::new(),
+            shown: 0,
             window,
-        }
+        })
     }
 
     fn resize(&mut self, size: PhysicalSize<u32>) {
-        self.surface_config.width = size.width;
-        self.surface_config.height = size.height;
-        self.surface.configure(&self.device, &self.surface_config);
+        if let Err(err) = self.renderer.resize(size) {
+            eprintln!("failed to resize renderer: {err:#}");
+        }
     }
 
-    fn render(&mut self) {
-        self.viewport.update(
-            &self.queue,
-            glyphon::Resolution {
-                width: self.surface_config.width,
-                height: self.surface_config.height,
-            },
-        );
-        self.text_renderer
-            .prepare(
-                &self.device,
-                &self.queue,
-                &mut self.font_system,
-                &mut self.atlas,
-                &self.viewport,
-                [glyphon::TextArea {
-                    buffer: &self.text_buffer,
-                    left: 0.0,
-                    top: 0.0,
-                    scale: self.window.scale_factor() as f32,
-                    bounds: glyphon::TextBounds {
-                        left: 0,
-                        top: 0,
-                        right: self.surface_config.width.try_into().unwrap(),
-                        bottom: self.surface_config.height.try_into().unwrap(),
-                    },
-                    default_color: TEXT_COLOR,
-                    custom_glyphs: &[],
-                }],
-                &mut self.swash_cache,
-            )
-            .unwrap();
-
-        let frame = self.surface.get_current_texture().unwrap();
-        let view = frame
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-        let mut encoder = self.device.create_command_encoder(&Default::default());
-        {
-            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: None,
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
+    fn render(&mut self) -> anyhow::Result<()> {
+        if !self.lifecycle.is_visible() {
+            return Ok(());
+        }
+        let frame = Frame {
+            revision: self.overlay.revision(),
+            size: self.window.surface_size(),
+            scale: self.window.scale_factor(),
+            opacity: self.lifecycle.opacity(),
+        };
+        if self.rendered == Some(frame) {
+            return Ok(());
+        }
+
+        self.overlay.render(
+            self.renderer.as_mut(),
+            frame.size,
+            frame.scale as f32,
+            frame.opacity,
+        )?;
+        self.rendered = Some(frame);
+        Ok(())
+    }
+
+    /// Advances the lifecycle, showing or hiding the window and scheduling
+    /// the next frame. Returns when to update again for the idle timeout.
+    fn update(&mut self) -> Option<Instant> {
+        let was_visible = self.window.is_visible().unwrap_or(true);
+        self.lifecycle.update();
+        let visible = self.lifecycle.is_visible();
+        if visible != was_visible {
+            self.window.set_visible(visible);
+            // hidden windows don't keep their contents
+            self.rendered = None;
+        }
+
+        if self.lifecycle.is_animating() {
+            self.window.request_redraw();
+        }
+        self.lifecycle.deadline()
+    }
+
+    /// Shows the captured text, as code if it looks like code given where
+    /// it's from.
+    fn set_selection(&mut self, capture: &Capture) {
+        let text = &capture.text;
+        self.cards = vec![match highlight::detect(capture) {
+            Some(language) => Card::code(text, language),
+            None => Card::text(text),
+        }];
+        self.show_card(0);
+    }
+
+    /// Puts cards from context providers ahead of the selection and shows
+    /// the best one.
+    fn add_cards(&mut self, cards: Vec<Card>) {
+        self.cards.splice(0..0, cards);
+        self.show_card(0);
+    }
+
+    /// Shows the next card, or the previous one if `backwards`.
+    fn cycle(&mut self, backwards: bool) {
+        let count = self.cards.len();
+        if count > 1 {
+            self.show_card(match backwards {
+                true => (self.shown + count - 1) % count,
+                false => (self.shown + 1) % count,
             });
+        }
+    }
 
-            self.text_renderer
-                .render(&self.atlas, &self.viewport, &mut pass)
-                .unwrap();
+    /// Shows the card at `index` in `cards`, with where it is among them in
+    /// the footer if there are others.
+    fn show_card(&mut self, index: usize) {
+        self.shown = index;
+        let mut card = self.cards[index].clone();
+        if self.cards.len() > 1 {
+            card.footer = format!("‹ {}/{} ›  ·  {}", index + 1, self.cards.len(), card.footer);
         }
+        self.set_card(card);
+    }
 
-        self.queue.submit(Some(encoder.finish()));
-        self.window.pre_present_notify();
-        frame.present();
+    fn set_card(&mut self, card: Card) {
+        let extent = self.overlay.set_card(card);
+        self.fit(extent);
+    }
 
-        self.atlas.trim();
+    fn set_theme(&mut self, theme: Theme) {
+        self.window.set_blur(theme.blur);
+        let extent = self.overlay.set_theme(theme);
+        self.fit(extent);
     }
 
-    fn set_text(&mut self, text: &str) {
-        self.text_buffer.set_text(
-            &mut self.font_system,
-            text,
-            &glyphon::Attrs::new().family(glyphon::Family::SansSerif),
-            glyphon::Shaping::Advanced,
-        );
-        self.text_buffer
-            .shape_until_scroll(&mut self.font_system, false);
+    fn pointer_moved(&mut self, position: PhysicalPosition<f64>) {
+        self.lifecycle.set_hovered(true);
+        let position = position.to_logical::<f32>(self.window.scale_factor());
+        if self.overlay.pointer_moved(position.x, position.y) {
+            self.window.request_redraw();
+        }
+    }
+
+    /// Returns the command of the button that was clicked, if any.
+    fn pointer_button(&mut self, state: ElementState) -> Option<Command> {
+        match state {
+            ElementState::Pressed => {
+                if self.overlay.pointer_pressed() {
+                    self.window.request_redraw();
+                } else if self.lifecycle.is_pinned()
+                    && let Err(err) = self.window.drag_window()
+                {
+                    eprintln!("failed to drag window: {err}");
+                }
+                None
+            }
+            ElementState::Released => {
+                self.window.request_redraw();
+                self.overlay.pointer_released()
+            }
+        }
+    }
+
+    fn scroll(&mut self, delta: MouseScrollDelta) {
+        // positive deltas move the content down, towards the top of the body
+        let delta = match delta {
+            MouseScrollDelta::LineDelta(_, lines) => lines * self.overlay.line_height(),
+            MouseScrollDelta::PixelDelta(position) => {
+                position.to_logical::<f32>(self.window.scale_factor()).y
+            }
+        };
+        if self.overlay.scroll_by(-delta) {
+            self.window.request_redraw();
+        }
+    }
+
+    /// Returns the command the key runs, if any.
+    fn key(&mut self, event: KeyEvent) -> Option<Command> {
+        if event.state != ElementState::Pressed {
+            return None;
+        }
+        match event.logical_key {
+            Key::Named(NamedKey::Tab) => {
+                self.overlay.focus_next(self.modifiers.shift_key());
+                self.window.request_redraw();
+                None
+            }
+            Key::Named(NamedKey::ArrowLeft) => {
+                self.cycle(true);
+                None
+            }
+            Key::Named(NamedKey::ArrowRight) => {
+                self.cycle(false);
+                None
+            }
+            Key::Named(NamedKey::Enter) => self.overlay.activate(),
+            Key::Named(NamedKey::Escape) => Some(Command::Dismiss),
+            _ => None,
+        }
+    }
+
+    fn fit(&mut self, extent: Extent) {
+        self.extent = extent;
+        let size = extent.to_physical(self.window.scale_factor());
+        if let Some(size) = self.window.request_surface_size(size.into()) {
+            self.resize(size);
+        }
+        self.window.request_redraw();
     }
 }
 
-fn create_window(event_loop: &dyn ActiveEventLoop) -> anyhow::Result<Arc<dyn Window>> {
+fn create_window(
+    event_loop: &dyn ActiveEventLoop,
+    size_limits: &SizeLimits,
+) -> anyhow::Result<Arc<dyn Window>> {
     let attrs = WindowAttributes::default()
-        .with_surface_size(LogicalSize::<u32>::from(WINDOW_SIZE))
+        .with_surface_size(LogicalSize::new(
+            size_limits.min_width,
+            size_limits.min_height,
+        ))
         // .with_resizable(false)
         .with_title(env!("CARGO_BIN_NAME"))
-        // .with_visible(false)
-        // .with_decorations(false)
+        .with_visible(false)
+        .with_decorations(false)
+        .with_transparent(true)
         .with_window_level(WindowLevel::AlwaysOnTop)
+        // a non-activating panel takes clicks and keys without stealing focus
+        // from the app the selection is in
         .with_active(false)
         .with_panel(true);
     Ok(Arc::from(
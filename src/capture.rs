@@ -0,0 +1,204 @@
+use std::ops::Range;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::redaction::Secret;
+
+/// A selection and everything known about where it was made.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Capture {
+    pub text: String,
+    /// The text as it was selected, if normalizing it changed it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original: Option<String>,
+    /// Selected ranges of the element's value, in UTF-16 code units.
+    pub ranges: Vec<Range<usize>>,
+    /// Where the selection is on screen.
+    pub bounds: Option<Bounds>,
+    pub timestamp: SystemTime,
+    pub trigger: Trigger,
+    pub pid: Option<i32>,
+    /// Bundle identifier of the app.
+    pub bundle_id: Option<String>,
+    pub app_name: Option<String>,
+    /// Title of the window the selection is in, from `AXTitle`.
+    pub window_title: Option<String>,
+    /// Path or URL of the document, from `AXDocument` or `AXURL`.
+    pub document: Option<String>,
+    /// The web page the selection is in, if it was made in a browser.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<Page>,
+    /// Role of the selected element, like `AXTextArea`.
+    pub role: Option<String>,
+    pub description: Option<String>,
+    /// Text around the selection, if a rule asked for it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<Context>,
+    /// Secrets that were masked, in the order they appeared.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redactions: Vec<Secret>,
+    /// Whether the selection is the previous capture with more selected,
+    /// which it supersedes.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub extends_previous: bool,
+    /// Index of the rule that applied, in `Config::rules`.
+    #[serde(skip)]
+    pub rule: Option<usize>,
+}
+
+impl Capture {
+    /// A capture of `text` with nothing known about it yet.
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    pub fn new(text: String, trigger: Trigger) -> Self {
+        Self {
+            text,
+            original: None,
+            ranges: Vec::new(),
+            bounds: None,
+            timestamp: SystemTime::now(),
+            trigger,
+            pid: None,
+            bundle_id: None,
+            app_name: None,
+            window_title: None,
+            document: None,
+            page: None,
+            role: None,
+            description: None,
+            context: None,
+            redactions: Vec::new(),
+            extends_previous: false,
+            rule: None,
+        }
+    }
+}
+
+/// Where a selection made in a browser comes from, for citing it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Page {
+    pub url: Option<String>,
+    pub title: Option<String>,
+    /// Target of the link the selection is in.
+    pub link: Option<String>,
+}
+
+/// The text either side of a selection.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Context {
+    pub before: String,
+    pub after: String,
+}
+
+/// A rectangle in screen points, with the origin at the top left of the
+/// main display.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Bounds {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// What made the capture happen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Trigger {
+    /// The selection changed between two polls.
+    Poll,
+    /// An accessibility notification reported the change.
+    Notification,
+    /// The pointer rested over the text.
+    Hover,
+    Hotkey,
+    /// The app doesn't expose the selection, so it was copied.
+    Copy,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+
+    #[test]
+    fn starts_with_nothing_known() {
+        let capture = Capture::new("hello".to_owned(), Trigger::Copy);
+        assert_eq!(capture.text, "hello");
+        assert_eq!(capture.trigger, Trigger::Copy);
+        assert!(capture.ranges.is_empty());
+        assert_eq!(capture.page, None);
+        assert_eq!(capture.context, None);
+        assert!(capture.redactions.is_empty());
+        assert!(!capture.extends_previous);
+        assert_eq!(capture.rule, None);
+    }
+
+    #[test]
+    fn leaves_out_what_is_unset() {
+        let capture = Capture::new("hello".to_owned(), Trigger::Poll);
+        let json = serde_json::to_value(&capture).unwrap();
+        let object = json.as_object().unwrap();
+        for field in [
+            "original",
+            "page",
+            "context",
+            "redactions",
+            "extends_previous",
+            "rule",
+        ] {
+            assert!(!object.contains_key(field), "{field}");
+        }
+        assert_eq!(json["trigger"], "poll");
+        // captures from before the fields were added still parse
+        assert_eq!(serde_json::from_value::<Capture>(json).unwrap(), capture);
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let capture = Capture {
+            original: Some("hello\u{200B}".to_owned()),
+            ranges: vec![0..5, 10..12],
+            bounds: Some(Bounds {
+                x: 1.0,
+                y: 2.0,
+                width: 3.0,
+                height: 4.5,
+            }),
+            timestamp: UNIX_EPOCH + Duration::from_millis(1_700_000_000_123),
+            pid: Some(42),
+            bundle_id: Some("com.apple.Safari".to_owned()),
+            app_name: Some("Safari".to_owned()),
+            window_title: Some("Example".to_owned()),
+            document: Some("https://example.com/".to_owned()),
+            page: Some(Page {
+                url: Some("https://example.com/".to_owned()),
+                title: Some("Example".to_owned()),
+                link: Some("https://example.com/more".to_owned()),
+            }),
+            role: Some("AXWebArea".to_owned()),
+            description: Some("HTML content".to_owned()),
+            context: Some(Context {
+                before: "say ".to_owned(),
+                after: " again".to_owned(),
+            }),
+            redactions: vec![Secret::Jwt, Secret::CardNumber],
+            extends_previous: true,
+            ..Capture::new("hello".to_owned(), Trigger::Hotkey)
+        };
+        let json = serde_json::to_string(&capture).unwrap();
+        assert!(
+            json.contains(r#""redactions":["jwt","card-number"]"#),
+            "{json}"
+        );
+        assert_eq!(serde_json::from_str::<Capture>(&json).unwrap(), capture);
+
+        // which rule applied only matters while it's processed
+        let with_rule = Capture {
+            rule: Some(3),
+            ..capture.clone()
+        };
+        let json = serde_json::to_string(&with_rule).unwrap();
+        assert_eq!(serde_json::from_str::<Capture>(&json).unwrap(), capture);
+    }
+}
@@ -0,0 +1,467 @@
+use crate::highlight::Language;
+use crate::layout::{Extent, Overflow, Rect};
+use crate::render::{Icon, Image};
+use crate::theme::{self, Theme};
+
+/// What the overlay shows: a header with an icon and the app name or provider
+/// title, the body, a row of action buttons and a footer with hints.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Card {
+    pub icon: Option<Icon>,
+    pub title: String,
+    pub body: String,
+    /// Language the body is highlighted as, if it's code.
+    pub language: Option<Language>,
+    /// Shown to the left of the body, like the color of a hex code.
+    pub image: Option<Image>,
+    pub actions: Vec<Action>,
+    pub footer: String,
+}
+
+impl Card {
+    /// Hints shown under cards in the overlay.
+    pub const FOOTER: &str = "⇥ next  ·  ⏎ run  ·  esc dismiss";
+
+    pub fn text(text: &str) -> Self {
+        Self {
+            icon: Some(Icon::Text),
+            title: "Selection".to_owned(),
+            body: text.to_owned(),
+            language: None,
+            image: preview(text),
+            actions: vec![
+                Action {
+                    label: "Copy".to_owned(),
+                    command: Command::Copy(text.to_owned()),
+                },
+                Action {
+                    label: "Pin".to_owned(),
+                    command: Command::Pin,
+                },
+            ],
+            footer: Self::FOOTER.to_owned(),
+        }
+    }
+
+    pub fn code(text: &str, language: Language) -> Self {
+        Self {
+            icon: Some(Icon::Code),
+            title: language.name().to_owned(),
+            language: Some(language),
+            ..Self::text(text)
+        }
+    }
+
+    /// Turns the card into one for a pinned window, which can't be pinned
+    /// again and is closed rather than dismissed.
+    pub fn pinned(mut self) -> Self {
+        self.actions.retain(|action| action.command != Command::Pin);
+        self.actions.push(Action {
+            label: "Close".to_owned(),
+            command: Command::Dismiss,
+        });
+        self.footer = "drag to move  ·  esc close".to_owned();
+        self
+    }
+}
+
+/// A picture of what `text` refers to, like the color of a hex code. Images
+/// at selected paths are left to the preview provider, which doesn't hold up
+/// the event loop loading them.
+fn preview(text: &str) -> Option<Image> {
+    let text = text.trim();
+    // `#123` is much more likely an issue or pull request than a color
+    if text
+        .strip_prefix('#')
+        .is_some_and(|number| number.len() == 3 && number.bytes().all(|byte| byte.is_ascii_digit()))
+    {
+        return None;
+    }
+    theme::parse_color(text).map(Image::solid)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Action {
+    pub label: String,
+    pub command: Command,
+}
+
+/// What running an [`Action`] does.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Copy(String),
+    Open(String),
+    /// Keeps the card on screen in a window of its own.
+    Pin,
+    Dismiss,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Section {
+    Header,
+    Body,
+    Footer,
+}
+
+impl Section {
+    pub const ALL: [Self; 3] = [Self::Header, Self::Body, Self::Footer];
+
+    pub fn style(self, theme: &Theme) -> SectionStyle {
+        let (scale, weight, color) = match self {
+            Self::Header => (0.5, glyphon::Weight::BOLD, theme.muted),
+            Self::Body => (1.0, glyphon::Weight::NORMAL, theme.foreground),
+            Self::Footer => (0.4, glyphon::Weight::NORMAL, theme.muted),
+        };
+        SectionStyle {
+            metrics: glyphon::Metrics::new(theme.font_size * scale, theme.line_height * scale),
+            weight,
+            color,
+        }
+    }
+
+    /// Header and footer are kept to a single line.
+    pub fn max_lines(self) -> Option<usize> {
+        match self {
+            Self::Header | Self::Footer => Some(1),
+            Self::Body => None,
+        }
+    }
+
+    /// The body scrolls, everything else is cut off.
+    pub fn overflow(self) -> Overflow {
+        match self {
+            Self::Header | Self::Footer => Overflow::Ellipsize,
+            Self::Body => Overflow::Scroll,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SectionStyle {
+    pub metrics: glyphon::Metrics,
+    pub weight: glyphon::Weight,
+    pub color: glyphon::Color,
+}
+
+/// The style of action button labels.
+pub fn button_style(theme: &Theme) -> SectionStyle {
+    SectionStyle {
+        metrics: glyphon::Metrics::new(theme.font_size * 0.45, theme.line_height * 0.45),
+        weight: glyphon::Weight::BOLD,
+        color: theme.accent,
+    }
+}
+
+/// The style of code bodies, which tend to have longer lines than prose.
+pub fn code_style(theme: &Theme) -> SectionStyle {
+    SectionStyle {
+        metrics: glyphon::Metrics::new(theme.font_size * 0.5, theme.line_height * 0.5),
+        weight: glyphon::Weight::NORMAL,
+        color: theme.foreground,
+    }
+}
+
+/// The measured parts of a card, `None` for the ones it doesn't have.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Measured {
+    pub icon_size: Option<f32>,
+    pub header: Option<Extent>,
+    pub image: Option<Extent>,
+    pub body: Option<Extent>,
+    /// Button labels.
+    pub actions: Vec<Extent>,
+    pub footer: Option<Extent>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Spacing {
+    pub gap: f32,
+    pub button_padding: f32,
+}
+
+/// Where each part of a card goes, relative to the top left corner of its
+/// content.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CardLayout {
+    pub icon: Option<Rect>,
+    pub header: Option<Rect>,
+    pub image: Option<Rect>,
+    pub body: Option<Rect>,
+    /// Buttons, with their labels inset by the button padding.
+    pub actions: Vec<Rect>,
+    pub footer: Option<Rect>,
+    pub extent: Extent,
+}
+
+impl CardLayout {
+    pub fn section(&self, section: Section) -> Option<Rect> {
+        match section {
+            Section::Header => self.header,
+            Section::Body => self.body,
+            Section::Footer => self.footer,
+        }
+    }
+
+    /// Returns the button at (`x`, `y`) in content coordinates.
+    pub fn action_at(&self, x: f32, y: f32) -> Option<usize> {
+        self.actions.iter().position(|rect| rect.contains(x, y))
+    }
+}
+
+/// Stacks the measured parts of a card, with the icon in front of the header,
+/// the image in front of the body and the buttons in a row between the body
+/// and the footer.
+pub fn layout_card(measured: &Measured, spacing: &Spacing) -> CardLayout {
+    let Spacing {
+        gap,
+        button_padding,
+    } = *spacing;
+    let mut layout = CardLayout::default();
+    let mut top = 0.0;
+
+    // starts a row of `height` below the previous one
+    let mut next_row = |height: f32| {
+        if top > 0.0 {
+            top += gap;
+        }
+        let row_top = top;
+        top += height;
+        row_top
+    };
+
+    if measured.icon_size.is_some() || measured.header.is_some() {
+        let icon_size = measured.icon_size.unwrap_or(0.0);
+        let header = measured.header.unwrap_or_default();
+        let row_height = icon_size.max(header.height);
+        let row_top = next_row(row_height);
+
+        let header_left = if icon_size > 0.0 {
+            layout.icon = Some(Rect {
+                x: 0.0,
+                y: row_top + (row_height - icon_size) / 2.0,
+                width: icon_size,
+                height: icon_size,
+            });
+            icon_size + gap
+        } else {
+            0.0
+        };
+        layout.header = Some(Rect {
+            x: header_left,
+            y: row_top + (row_height - header.height) / 2.0,
+            width: header.width,
+            height: header.height,
+        });
+        layout.extent.width = header_left + header.width;
+    }
+
+    if measured.image.is_some() || measured.body.is_some() {
+        let body = measured.body.unwrap_or_default();
+        let row_height = measured
+            .image
+            .map_or(0.0, |image| image.height)
+            .max(body.height);
+        let row_top = next_row(row_height);
+
+        let body_left = match measured.image {
+            Some(image) => {
+                layout.image = Some(Rect {
+                    x: 0.0,
+                    y: row_top,
+                    width: image.width,
+                    height: image.height,
+                });
+                image.width + gap
+            }
+            None => 0.0,
+        };
+        if measured.body.is_some() {
+            layout.body = Some(Rect {
+                x: body_left,
+                y: row_top,
+                width: body.width,
+                height: body.height,
+            });
+        }
+        layout.extent.width = layout.extent.width.max(body_left + body.width);
+    }
+
+    if !measured.actions.is_empty() {
+        let buttons = measured
+            .actions
+            .iter()
+            .map(|label| label.grow(button_padding));
+        let row_height = buttons
+            .clone()
+            .map(|button| button.height)
+            .fold(0.0, f32::max);
+        let row_top = next_row(row_height);
+
+        let mut left = 0.0;
+        for button in buttons {
+            layout.actions.push(Rect {
+                x: left,
+                y: row_top,
+                width: button.width,
+                height: row_height,
+            });
+            left += button.width + gap;
+        }
+        layout.extent.width = layout.extent.width.max(left - gap);
+    }
+
+    if let Some(footer) = measured.footer {
+        let row_top = next_row(footer.height);
+        layout.footer = Some(Rect {
+            x: 0.0,
+            y: row_top,
+            width: footer.width,
+            height: footer.height,
+        });
+        layout.extent.width = layout.extent.width.max(footer.width);
+    }
+
+    layout.extent.height = top;
+    layout
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPACING: Spacing = Spacing {
+        gap: 8.0,
+        button_padding: 4.0,
+    };
+
+    fn extent(width: f32, height: f32) -> Extent {
+        Extent { width, height }
+    }
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn stacks_every_part_with_a_gap_between() {
+        let layout = layout_card(
+            &Measured {
+                icon_size: Some(16.0),
+                header: Some(extent(100.0, 12.0)),
+                image: Some(extent(40.0, 40.0)),
+                body: Some(extent(200.0, 60.0)),
+                actions: vec![extent(30.0, 10.0), extent(20.0, 10.0)],
+                footer: Some(extent(150.0, 10.0)),
+            },
+            &SPACING,
+        );
+
+        // the shorter of icon and header is centered in the row
+        assert_eq!(layout.icon, Some(rect(0.0, 0.0, 16.0, 16.0)));
+        assert_eq!(layout.header, Some(rect(24.0, 2.0, 100.0, 12.0)));
+        assert_eq!(layout.image, Some(rect(0.0, 24.0, 40.0, 40.0)));
+        assert_eq!(layout.body, Some(rect(48.0, 24.0, 200.0, 60.0)));
+        assert_eq!(
+            layout.actions,
+            [rect(0.0, 92.0, 38.0, 18.0), rect(46.0, 92.0, 28.0, 18.0)]
+        );
+        assert_eq!(layout.footer, Some(rect(0.0, 118.0, 150.0, 10.0)));
+        assert_eq!(layout.extent, extent(248.0, 128.0));
+    }
+
+    #[test]
+    fn leaves_out_missing_parts_without_gaps() {
+        let layout = layout_card(
+            &Measured {
+                body: Some(extent(200.0, 60.0)),
+                footer: Some(extent(250.0, 10.0)),
+                ..Measured::default()
+            },
+            &SPACING,
+        );
+
+        assert_eq!(layout.icon, None);
+        assert_eq!(layout.header, None);
+        assert_eq!(layout.image, None);
+        assert_eq!(layout.body, Some(rect(0.0, 0.0, 200.0, 60.0)));
+        assert!(layout.actions.is_empty());
+        assert_eq!(layout.footer, Some(rect(0.0, 68.0, 250.0, 10.0)));
+        assert_eq!(layout.extent, extent(250.0, 78.0));
+    }
+
+    #[test]
+    fn puts_the_header_first_without_an_icon() {
+        let layout = layout_card(
+            &Measured {
+                header: Some(extent(100.0, 12.0)),
+                ..Measured::default()
+            },
+            &SPACING,
+        );
+
+        assert_eq!(layout.header, Some(rect(0.0, 0.0, 100.0, 12.0)));
+        assert_eq!(layout.extent, extent(100.0, 12.0));
+    }
+
+    #[test]
+    fn gives_an_image_without_a_body_a_row_of_its_own() {
+        let layout = layout_card(
+            &Measured {
+                image: Some(extent(40.0, 40.0)),
+                ..Measured::default()
+            },
+            &SPACING,
+        );
+
+        assert_eq!(layout.image, Some(rect(0.0, 0.0, 40.0, 40.0)));
+        assert_eq!(layout.body, None);
+        assert_eq!(layout.extent, extent(48.0, 40.0));
+    }
+
+    #[test]
+    fn finds_the_button_under_a_point() {
+        let layout = layout_card(
+            &Measured {
+                actions: vec![extent(30.0, 10.0), extent(20.0, 10.0)],
+                ..Measured::default()
+            },
+            &SPACING,
+        );
+
+        assert_eq!(layout.action_at(0.0, 0.0), Some(0));
+        assert_eq!(layout.action_at(37.9, 17.9), Some(0));
+        assert_eq!(layout.action_at(42.0, 9.0), None);
+        assert_eq!(layout.action_at(46.0, 9.0), Some(1));
+        assert_eq!(layout.action_at(46.0, 18.0), None);
+    }
+
+    #[test]
+    fn previews_hex_colors() {
+        let swatch = |r, g, b| Some(Image::solid(glyphon::Color::rgb(r, g, b)));
+        assert_eq!(preview(" #3366ff\n"), swatch(0x33, 0x66, 0xff));
+        assert_eq!(preview("#fa0"), swatch(0xff, 0xaa, 0x00));
+        assert_eq!(
+            preview("#3366ff80"),
+            Some(Image::solid(glyphon::Color::rgba(0x33, 0x66, 0xff, 0x80)))
+        );
+    }
+
+    #[test]
+    fn leaves_issue_references_and_partial_colors_alone() {
+        for text in ["#123", "see #abc", "#3366ff.", "#12345", "#+ff", "3366ff"] {
+            assert_eq!(preview(text), None, "{text}");
+        }
+    }
+
+    #[test]
+    fn previews_nothing_for_other_text() {
+        for text in ["😀", "é12", "#é1", ""] {
+            assert_eq!(Card::text(text).image, None, "{text}");
+        }
+    }
+}
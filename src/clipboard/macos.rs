@@ -0,0 +1,170 @@
+use std::ffi::{c_ulong, c_void};
+use std::ptr::NonNull;
+
+use objc2::rc::Retained;
+use objc2::runtime::ProtocolObject;
+use objc2_app_kit::{NSPasteboard, NSPasteboardItem, NSPasteboardTypeString, NSPasteboardWriting};
+use objc2_core_foundation::{CFData, CFRetained, CFString, CFType};
+use objc2_core_graphics::{CGEvent, CGEventFlags, CGEventTapLocation};
+use objc2_foundation::{NSArray, NSData, NSString};
+
+use super::{Keyboard, Pasteboard, Snapshot};
+
+/// Virtual key code of the C key in the ANSI layout, for layouts that have
+/// no key typing `c`.
+const ANSI_KEY_C: u16 = 8;
+
+/// `kUCKeyActionDisplay` and `kUCKeyTranslateNoDeadKeysMask`, to look up
+/// what a key types on its own.
+const KEY_ACTION_DISPLAY: u16 = 3;
+const NO_DEAD_KEYS: u32 = 1;
+
+#[link(name = "Carbon", kind = "framework")]
+unsafe extern "C" {
+    static kTISPropertyUnicodeKeyLayoutData: &'static CFString;
+
+    fn TISCopyCurrentKeyboardLayoutInputSource() -> Option<NonNull<CFType>>;
+    fn TISGetInputSourceProperty(source: &CFType, key: &CFString) -> Option<NonNull<CFData>>;
+    fn LMGetKbdType() -> u8;
+    fn UCKeyTranslate(
+        layout: *const c_void,
+        virtual_key_code: u16,
+        key_action: u16,
+        modifier_key_state: u32,
+        keyboard_type: u32,
+        key_translate_options: u32,
+        dead_key_state: *mut u32,
+        max_string_length: c_ulong,
+        actual_string_length: *mut c_ulong,
+        unicode_string: *mut u16,
+    ) -> i32;
+}
+
+/// The general pasteboard, which copy and paste go through.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemPasteboard;
+
+impl SystemPasteboard {
+    pub fn set_string(&self, text: &str) {
+        unsafe {
+            let pasteboard = NSPasteboard::generalPasteboard();
+            pasteboard.clearContents();
+            pasteboard.setString_forType(&NSString::from_str(text), NSPasteboardTypeString);
+        }
+    }
+}
+
+impl Pasteboard for SystemPasteboard {
+    fn change_count(&self) -> isize {
+        unsafe { NSPasteboard::generalPasteboard().changeCount() }
+    }
+
+    fn string(&self) -> Option<String> {
+        unsafe { NSPasteboard::generalPasteboard().stringForType(NSPasteboardTypeString) }
+            .map(|string| string.to_string())
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let Some(items) = (unsafe { NSPasteboard::generalPasteboard().pasteboardItems() }) else {
+            return Snapshot::default();
+        };
+        let items = items
+            .iter()
+            .map(|item| {
+                unsafe { item.types() }
+                    .iter()
+                    .filter_map(|kind| {
+                        let data = unsafe { item.dataForType(&kind) }?;
+                        Some((kind.to_string(), data.to_vec()))
+                    })
+                    .collect()
+            })
+            .collect();
+        Snapshot { items }
+    }
+
+    fn restore(&self, snapshot: &Snapshot) {
+        let items: Vec<Retained<ProtocolObject<dyn NSPasteboardWriting>>> = snapshot
+            .items
+            .iter()
+            .map(|kinds| {
+                let item = unsafe { NSPasteboardItem::new() };
+                for (kind, data) in kinds {
+                    unsafe {
+                        item.setData_forType(&NSData::with_bytes(data), &NSString::from_str(kind))
+                    };
+                }
+                ProtocolObject::from_retained(item)
+            })
+            .collect();
+        unsafe {
+            let pasteboard = NSPasteboard::generalPasteboard();
+            pasteboard.clearContents();
+            pasteboard.writeObjects(&NSArray::from_retained_slice(&items));
+        }
+    }
+}
+
+/// Posts key presses as if they were typed.
+#[derive(Clone, Copy, Debug)]
+pub struct SystemKeyboard {
+    /// Key code of the key that types `c`, which depends on the layout.
+    key_c: u16,
+}
+
+impl SystemKeyboard {
+    /// Looks up the keys to press in the current keyboard layout, which has
+    /// to be done on the main thread.
+    pub fn current() -> Self {
+        Self {
+            key_c: key_code('c').unwrap_or(ANSI_KEY_C),
+        }
+    }
+}
+
+impl Keyboard for SystemKeyboard {
+    fn copy(&self) {
+        for key_down in [true, false] {
+            let Some(event) = (unsafe { CGEvent::new_keyboard_event(None, self.key_c, key_down) })
+            else {
+                continue;
+            };
+            unsafe {
+                CGEvent::set_flags(Some(&event), CGEventFlags::MaskCommand);
+                CGEvent::post(CGEventTapLocation::HIDEventTap, Some(&event));
+            }
+        }
+    }
+}
+
+/// Finds the key that types `character` without modifiers in the current
+/// keyboard layout.
+fn key_code(character: char) -> Option<u16> {
+    let source = unsafe { TISCopyCurrentKeyboardLayoutInputSource() }?;
+    // SAFETY: the source is returned with a reference that's ours to release
+    let source: CFRetained<CFType> = unsafe { CFRetained::from_raw(source) };
+    let data = unsafe { TISGetInputSourceProperty(&source, kTISPropertyUnicodeKeyLayoutData) }?;
+    let layout = unsafe { data.as_ref() }.byte_ptr();
+    let keyboard_type = u32::from(unsafe { LMGetKbdType() });
+
+    let mut expected = [0; 2];
+    let expected = character.encode_utf16(&mut expected);
+    (0..128).find(|&code| {
+        let (mut dead_keys, mut length, mut typed) = (0, 0, [0u16; 4]);
+        let status = unsafe {
+            UCKeyTranslate(
+                layout.cast(),
+                code,
+                KEY_ACTION_DISPLAY,
+                0,
+                keyboard_type,
+                NO_DEAD_KEYS,
+                &mut dead_keys,
+                typed.len() as c_ulong,
+                &mut length,
+                typed.as_mut_ptr(),
+            )
+        };
+        status == 0 && typed.get(..length as usize) == Some(&*expected)
+    })
+}
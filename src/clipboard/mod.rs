@@ -0,0 +1,236 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+#[cfg(target_os = "macos")]
+pub use self::macos::{SystemKeyboard, SystemPasteboard};
+
+#[cfg(target_os = "macos")]
+mod macos;
+
+/// How often the pasteboard is checked for the copy to arrive.
+const CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Whether selections that apps don't expose are read by copying them, and
+/// how long the app gets to copy.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default)]
+pub struct FallbackConfig {
+    pub enabled: bool,
+    pub timeout_ms: u64,
+}
+
+impl Default for FallbackConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout_ms: 250,
+        }
+    }
+}
+
+/// Everything on a pasteboard: the data of each type of each item.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Snapshot {
+    pub items: Vec<Vec<(String, Vec<u8>)>>,
+}
+
+pub trait Pasteboard {
+    /// Goes up whenever the contents change.
+    fn change_count(&self) -> isize;
+
+    fn string(&self) -> Option<String>;
+
+    fn snapshot(&self) -> Snapshot;
+
+    /// Replaces the contents with `snapshot`.
+    fn restore(&self, snapshot: &Snapshot);
+}
+
+pub trait Keyboard {
+    /// Sends the copy shortcut to the focused app.
+    fn copy(&self);
+}
+
+/// Reads the selection of apps that don't expose it by having them copy it,
+/// then putting back whatever was on the pasteboard.
+pub struct CopyFallback<P, K> {
+    pasteboard: P,
+    keyboard: K,
+    timeout: Duration,
+}
+
+impl<P: Pasteboard, K: Keyboard> CopyFallback<P, K> {
+    pub fn new(pasteboard: P, keyboard: K, config: FallbackConfig) -> Self {
+        Self {
+            pasteboard,
+            keyboard,
+            timeout: Duration::from_millis(config.timeout_ms),
+        }
+    }
+
+    /// Copies the selection of the focused app, returning `None` if it
+    /// didn't copy any text in time.
+    pub fn selected_text(&self) -> Option<String> {
+        let snapshot = self.pasteboard.snapshot();
+        let before = self.pasteboard.change_count();
+        self.keyboard.copy();
+
+        let text = if self.changed_since(before) {
+            self.pasteboard.string()
+        } else {
+            // an app that copies just after the timeout mustn't leave the
+            // selection in place of what was there, so it gets as long again
+            self.changed_since(before);
+            None
+        };
+        if self.pasteboard.change_count() != before {
+            self.pasteboard.restore(&snapshot);
+        }
+        text
+    }
+
+    /// Waits up to the timeout for the pasteboard to change from `before`,
+    /// returning whether it did.
+    fn changed_since(&self, before: isize) -> bool {
+        let deadline = Instant::now() + self.timeout;
+        while self.pasteboard.change_count() == before {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(CHECK_INTERVAL);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    const TEXT: &str = "public.utf8-plain-text";
+
+    /// A pasteboard kept in memory, shared with the keyboard that copies to
+    /// it.
+    #[derive(Clone, Default)]
+    struct FakePasteboard(Arc<Mutex<(isize, Snapshot)>>);
+
+    impl FakePasteboard {
+        fn set(&self, snapshot: Snapshot) {
+            let mut state = self.0.lock().unwrap();
+            *state = (state.0 + 1, snapshot);
+        }
+    }
+
+    impl Pasteboard for FakePasteboard {
+        fn change_count(&self) -> isize {
+            self.0.lock().unwrap().0
+        }
+
+        fn string(&self) -> Option<String> {
+            let state = self.0.lock().unwrap();
+            let (_, data) = state
+                .1
+                .items
+                .first()?
+                .iter()
+                .find(|(kind, _)| kind == TEXT)?;
+            String::from_utf8(data.clone()).ok()
+        }
+
+        fn snapshot(&self) -> Snapshot {
+            self.0.lock().unwrap().1.clone()
+        }
+
+        fn restore(&self, snapshot: &Snapshot) {
+            self.set(snapshot.clone());
+        }
+    }
+
+    /// Copies `text` to the pasteboard after `delay`, or nothing at all.
+    struct FakeKeyboard {
+        pasteboard: FakePasteboard,
+        text: Option<&'static str>,
+        delay: Duration,
+    }
+
+    impl Keyboard for FakeKeyboard {
+        fn copy(&self) {
+            let Some(text) = self.text else {
+                return;
+            };
+            let (pasteboard, delay) = (self.pasteboard.clone(), self.delay);
+            let copy = move || {
+                thread::sleep(delay);
+                pasteboard.set(Snapshot {
+                    items: vec![vec![(TEXT.to_owned(), text.as_bytes().to_vec())]],
+                });
+            };
+            match delay.is_zero() {
+                true => copy(),
+                false => drop(thread::spawn(copy)),
+            }
+        }
+    }
+
+    /// A pasteboard with two items, one of them in several types, and a
+    /// fallback that times out after 50 ms.
+    fn fallback(
+        text: Option<&'static str>,
+        delay: Duration,
+    ) -> (
+        CopyFallback<FakePasteboard, FakeKeyboard>,
+        FakePasteboard,
+        Snapshot,
+    ) {
+        let original = Snapshot {
+            items: vec![
+                vec![
+                    (TEXT.to_owned(), b"user's text".to_vec()),
+                    ("public.rtf".to_owned(), b"{\\rtf1 user's text}".to_vec()),
+                ],
+                vec![("public.png".to_owned(), vec![0x89, b'P', b'N', b'G'])],
+            ],
+        };
+        let pasteboard = FakePasteboard::default();
+        pasteboard.set(original.clone());
+        let keyboard = FakeKeyboard {
+            pasteboard: pasteboard.clone(),
+            text,
+            delay,
+        };
+        let config = FallbackConfig {
+            enabled: true,
+            timeout_ms: 50,
+        };
+        let fallback = CopyFallback::new(pasteboard.clone(), keyboard, config);
+        (fallback, pasteboard, original)
+    }
+
+    #[test]
+    fn restores_every_type_of_every_item() {
+        let (fallback, pasteboard, original) = fallback(Some("selection"), Duration::ZERO);
+        assert_eq!(fallback.selected_text().as_deref(), Some("selection"));
+        assert_eq!(pasteboard.snapshot(), original);
+    }
+
+    #[test]
+    fn leaves_the_pasteboard_alone_if_nothing_is_copied() {
+        let (fallback, pasteboard, original) = fallback(None, Duration::ZERO);
+        let before = pasteboard.change_count();
+        assert_eq!(fallback.selected_text(), None);
+        assert_eq!(pasteboard.snapshot(), original);
+        assert_eq!(pasteboard.change_count(), before);
+    }
+
+    #[test]
+    fn restores_after_a_copy_that_comes_too_late() {
+        let (fallback, pasteboard, original) =
+            fallback(Some("selection"), Duration::from_millis(80));
+        assert_eq!(fallback.selected_text(), None);
+        assert_eq!(pasteboard.snapshot(), original);
+    }
+}
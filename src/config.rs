@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::clipboard::FallbackConfig;
+use crate::fonts::FontConfig;
+use crate::history::Retention;
+use crate::layout::SizeLimits;
+use crate::lifecycle::Timing;
+use crate::normalization::Normalization;
+use crate::provider::ProviderConfig;
+use crate::redaction::Redaction;
+use crate::rules::Rules;
+use crate::sink::SinkConfig;
+use crate::sink::outbox::OutboxConfig;
+use crate::stabilizer::Stabilization;
+use crate::theme::{Appearance, Theme};
+
+const SYSTEM_THEME: &str = "system";
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Name of the theme to use, or `"system"` to follow the appearance.
+    pub theme: String,
+    pub light_theme: String,
+    pub dark_theme: String,
+    /// Themes by name, which only need to set what differs from the built-in
+    /// theme they're based on: the one of the same name, or else the dark
+    /// theme for the one used as `dark_theme` and the light theme for others.
+    #[serde(rename = "themes")]
+    theme_overrides: HashMap<String, toml::Table>,
+    /// The themes of the config file, based on built-in ones.
+    #[serde(skip)]
+    pub themes: HashMap<String, Theme>,
+    pub size: SizeLimits,
+    pub timing: Timing,
+    pub fonts: FontConfig,
+    pub stabilization: Stabilization,
+    /// Whether selections apps don't expose are read by copying them.
+    pub copy_fallback: FallbackConfig,
+    /// How captures are handled depending on where they're from.
+    pub rules: Rules,
+    /// How selected text is cleaned up.
+    pub normalization: Normalization,
+    /// Which secrets are kept out of captures.
+    pub redaction: Redaction,
+    /// Which context providers suggest cards, and how long they get.
+    pub providers: ProviderConfig,
+    /// How long captures are kept in the history.
+    pub history: Retention,
+    /// Where else captures are delivered.
+    pub sinks: Vec<SinkConfig>,
+    /// How captures that couldn't be delivered to a sink are retried.
+    pub outbox: OutboxConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            theme: SYSTEM_THEME.to_owned(),
+            light_theme: "light".to_owned(),
+            dark_theme: "dark".to_owned(),
+            theme_overrides: HashMap::new(),
+            themes: HashMap::new(),
+            size: SizeLimits::default(),
+            timing: Timing::default(),
+            fonts: FontConfig::default(),
+            stabilization: Stabilization::default(),
+            copy_fallback: FallbackConfig::default(),
+            rules: Rules::default(),
+            normalization: Normalization::default(),
+            redaction: Redaction::default(),
+            providers: ProviderConfig::default(),
+            history: Retention::default(),
+            sinks: Vec::new(),
+            outbox: OutboxConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        Some(
+            dirs::config_dir()?
+                .join(env!("CARGO_PKG_NAME"))
+                .join("config.toml"),
+        )
+    }
+
+    /// Loads the config file, falling back to the defaults if there is none.
+    pub fn load() -> anyhow::Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read {}", path.display()));
+            }
+        };
+
+        Self::parse(&contents).with_context(|| format!("failed to parse {}", path.display()))
+    }
+
+    fn parse(contents: &str) -> anyhow::Result<Self> {
+        let mut config: Self = toml::from_str(contents)?;
+        for (name, overrides) in &config.theme_overrides {
+            let base = Theme::builtin(name).unwrap_or_else(|| {
+                if *name == config.dark_theme {
+                    Theme::dark()
+                } else {
+                    Theme::light()
+                }
+            });
+            let theme = base
+                .merge(overrides)
+                .with_context(|| format!("invalid theme {name:?}"))?;
+            config.themes.insert(name.clone(), theme);
+        }
+        for name in [&config.light_theme, &config.dark_theme]
+            .into_iter()
+            .chain((config.theme != SYSTEM_THEME).then_some(&config.theme))
+        {
+            anyhow::ensure!(config.named_theme(name).is_some(), "unknown theme {name:?}");
+        }
+        let mut outboxes = HashMap::new();
+        for sink in &config.sinks {
+            if let Some(other) = outboxes.insert(sink.outbox_dir(), sink.name()) {
+                anyhow::bail!(
+                    "sinks {other:?} and {:?} would share an outbox, give them different names",
+                    sink.name()
+                );
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn follows_appearance(&self) -> bool {
+        self.theme == SYSTEM_THEME
+    }
+
+    /// Resolves the theme to use under the given system appearance.
+    pub fn theme(&self, appearance: Appearance) -> Theme {
+        let name = match appearance {
+            _ if !self.follows_appearance() => &self.theme,
+            Appearance::Light => &self.light_theme,
+            Appearance::Dark => &self.dark_theme,
+        };
+        self.named_theme(name).unwrap_or_else(|| match appearance {
+            Appearance::Light => Theme::light(),
+            Appearance::Dark => Theme::dark(),
+        })
+    }
+
+    fn named_theme(&self, name: &str) -> Option<Theme> {
+        self.themes
+            .get(name)
+            .cloned()
+            .or_else(|| Theme::builtin(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme::SyntaxColors;
+
+    #[test]
+    fn fills_in_themes_from_the_builtin_they_override() {
+        let config = Config::parse(
+            r##"
+            [themes.dark]
+            accent = "#ff9500"
+            syntax.keyword = "#ffffff"
+            "##,
+        )
+        .unwrap();
+        let dark = config.theme(Appearance::Dark);
+        assert_eq!(dark.accent, glyphon::Color::rgb(0xff, 0x95, 0x00));
+        assert_eq!(dark.syntax.keyword, glyphon::Color::rgb(0xff, 0xff, 0xff));
+        assert_eq!(
+            Theme {
+                accent: Theme::dark().accent,
+                syntax: SyntaxColors {
+                    keyword: Theme::dark().syntax.keyword,
+                    ..dark.syntax.clone()
+                },
+                ..dark
+            },
+            Theme::dark()
+        );
+        assert_eq!(config.theme(Appearance::Light), Theme::light());
+    }
+
+    #[test]
+    fn bases_other_themes_on_the_appearance_they_are_used_for() {
+        let config = Config::parse(
+            r##"
+            light_theme = "paper"
+            dark_theme = "midnight"
+
+            [themes.paper]
+            font_size = 28.0
+
+            [themes.midnight]
+            background = "#000000"
+            "##,
+        )
+        .unwrap();
+        let paper = config.theme(Appearance::Light);
+        assert_eq!(paper.font_size, 28.0);
+        assert_eq!(paper.background, Theme::light().background);
+        let midnight = config.theme(Appearance::Dark);
+        assert_eq!(midnight.background, glyphon::Color::rgb(0, 0, 0));
+        assert_eq!(midnight.foreground, Theme::dark().foreground);
+    }
+
+    #[test]
+    fn rejects_invalid_and_unknown_themes() {
+        let err = Config::parse("[themes.dark]\naccent = \"blue\"").unwrap_err();
+        assert!(
+            format!("{err:#}").contains("invalid theme \"dark\""),
+            "{err:#}"
+        );
+        assert!(Config::parse("[themes.dark]\nfont_size = \"big\"").is_err());
+        assert!(Config::parse("theme = \"solarized\"").is_err());
+    }
+
+    #[test]
+    fn rejects_sinks_sharing_an_outbox() {
+        let sinks = |sinks: &str| Config::parse(sinks).map(|config| config.sinks.len());
+        let notify = "[[sinks]]\ntype = \"command\"\nprogram = \"notify\"\n";
+        assert!(sinks(&notify.repeat(2)).is_err());
+        assert!(sinks(&format!("{notify}args = [\"-u\"]\n{notify}")).is_err());
+        assert_eq!(
+            sinks(&format!("{notify}name = \"urgent\"\n{notify}")).unwrap(),
+            2
+        );
+
+        // names are made safe for the file system first
+        let err = sinks(&format!("{notify}name = \"a/b\"\n{notify}name = \"a b\"\n")).unwrap_err();
+        assert!(
+            err.to_string().contains(r#""a/b" and "a b" would share"#),
+            "{err:#}"
+        );
+    }
+}
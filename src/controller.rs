@@ -1,19 +1,53 @@
+use std::ops::Range;
 use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use objc2_app_kit::NSRunningApplication;
+use objc2_app_kit::{NSEvent, NSRunningApplication};
 use objc2_application_services::{AXError, AXIsProcessTrusted, AXUIElement, AXValue};
-use objc2_core_foundation::{CFRetained, CFString, CGRect};
+use objc2_core_foundation::{CFArray, CFRange, CFRetained, CFString, CFURL, CGRect, Type};
+use objc2_core_graphics::{CGEventSource, CGEventSourceStateID, CGEventType};
+use winit::event_loop::EventLoopProxy;
 
 use crate::accessibility::{AXUIElementExt, AXValueExt, attribute};
+use crate::capture::{Bounds, Capture, Page, Trigger};
+use crate::clipboard::{CopyFallback, FallbackConfig, SystemKeyboard, SystemPasteboard};
 use crate::event::Event;
+use crate::lifecycle::Clock;
+use crate::normalization::Normalization;
+use crate::redaction::Redaction;
+use crate::rules::{Action, Rules};
+use crate::stabilizer::Stabilizer;
+
+/// Subrole of password fields, whose value is never read.
+const SECURE_TEXT_FIELD: &str = "AXSecureTextField";
+
+/// Roles of the root of a web page in a browser, and of links in it.
+const WEB_AREA: &str = "AXWebArea";
+const LINK: &str = "AXLink";
+/// How far up from the selected element the web page is looked for.
+const MAX_DEPTH: usize = 64;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
 
 pub struct Controller {
     pid: libc::pid_t,
     system_wide: CFRetained<AXUIElement>,
+    rules: Rules,
+    normalization: Normalization,
+    redaction: Redaction,
+    fallback: FallbackConfig,
+    copy_fallback: CopyFallback<SystemPasteboard, SystemKeyboard>,
 }
 
 impl Controller {
-    pub fn new() -> Self {
+    pub fn new(
+        rules: Rules,
+        normalization: Normalization,
+        redaction: Redaction,
+        fallback: FallbackConfig,
+        keyboard: SystemKeyboard,
+    ) -> Self {
         assert!(unsafe { AXIsProcessTrusted() });
 
         let application = unsafe { NSRunningApplication::currentApplication() };
@@ -21,7 +55,15 @@ impl Controller {
 
         let system_wide = unsafe { AXUIElement::new_system_wide() };
 
-        Self { pid, system_wide }
+        Self {
+            pid,
+            system_wide,
+            rules,
+            normalization,
+            redaction,
+            fallback,
+            copy_fallback: CopyFallback::new(SystemPasteboard, keyboard, fallback),
+        }
     }
 
     pub fn focused_app(&self) -> Result<CFRetained<AXUIElement>, AXError> {
@@ -64,7 +106,381 @@ impl Controller {
         Ok(bounds)
     }
 
-    pub fn run(&self, event_tx: mpsc::Sender<Event>) {
-        todo!()
+    /// Captures `text`, selected in `element` of `app`, with as much
+    /// provenance as the app exposes.
+    pub fn capture(
+        &self,
+        app: &AXUIElement,
+        element: &AXUIElement,
+        text: String,
+        trigger: Trigger,
+    ) -> Capture {
+        let pid = AXUIElementExt::pid(app).ok();
+        let application = pid.and_then(|pid| unsafe {
+            NSRunningApplication::runningApplicationWithProcessIdentifier(pid)
+        });
+        let window = element
+            .attribute_value(&CFString::from_static_str(attribute::kAXWindowAttribute))
+            .or_else(|_| {
+                app.attribute_value(&CFString::from_static_str(
+                    attribute::kAXFocusedWindowAttribute,
+                ))
+            })
+            .ok()
+            .and_then(|window| window.downcast::<AXUIElement>().ok());
+        let document = window.as_deref().and_then(|window| {
+            string_attribute(window, attribute::kAXDocumentAttribute)
+                .or_else(|| url_attribute(window, attribute::kAXURLAttribute))
+        });
+
+        Capture {
+            ranges: self.selected_text_ranges(element),
+            bounds: self
+                .selected_text_bounds(element)
+                .ok()
+                .flatten()
+                .map(|rect| Bounds {
+                    x: rect.origin.x,
+                    y: rect.origin.y,
+                    width: rect.size.width,
+                    height: rect.size.height,
+                }),
+            pid,
+            bundle_id: application
+                .as_ref()
+                .and_then(|application| unsafe { application.bundleIdentifier() })
+                .map(|id| id.to_string()),
+            app_name: application
+                .as_ref()
+                .and_then(|application| unsafe { application.localizedName() })
+                .map(|name| name.to_string()),
+            window_title: window
+                .as_deref()
+                .and_then(|window| string_attribute(window, attribute::kAXTitleAttribute)),
+            document,
+            page: page(&selection_start(element).unwrap_or_else(|| element.retain())),
+            role: string_attribute(element, attribute::kAXRoleAttribute),
+            description: string_attribute(element, attribute::kAXDescriptionAttribute)
+                .or_else(|| string_attribute(element, attribute::kAXRoleDescriptionAttribute)),
+            ..Capture::new(text, trigger)
+        }
+    }
+
+    /// Returns the selected ranges of `element`, falling back to the single
+    /// range for elements that don't support several.
+    fn selected_text_ranges(&self, element: &AXUIElement) -> Vec<Range<usize>> {
+        let values: Vec<CFRetained<AXValue>> = match element.attribute_value(
+            &CFString::from_static_str(attribute::kAXSelectedTextRangesAttribute),
+        ) {
+            Ok(ranges) => {
+                let Ok(ranges) = ranges.downcast::<CFArray>() else {
+                    return Vec::new();
+                };
+                let ranges: CFRetained<CFArray<AXValue>> =
+                    unsafe { CFRetained::cast_unchecked(ranges) };
+                (0..ranges.len())
+                    .filter_map(|index| ranges.get(index))
+                    .collect()
+            }
+            Err(_) => element
+                .attribute_value(&CFString::from_static_str(
+                    attribute::kAXSelectedTextRangeAttribute,
+                ))
+                .ok()
+                .and_then(|range| range.downcast::<AXValue>().ok())
+                .into_iter()
+                .collect(),
+        };
+        values
+            .iter()
+            .filter_map(|value| value.get_value::<CFRange>())
+            .map(|range| range.location as usize..(range.location + range.length) as usize)
+            .collect()
+    }
+
+    /// Runs `capture` of the selection in `element` through the rules,
+    /// normalization and redaction, returning `None` if it's dropped.
+    fn process(&self, capture: Capture, element: Option<&AXUIElement>) -> Option<Capture> {
+        let value =
+            || element.and_then(|element| string_attribute(element, attribute::kAXValueAttribute));
+        self.rules
+            .process(capture, value, &self.normalization, &self.redaction)
+    }
+
+    /// Whether the selection of `element` in `app` may be copied to read it:
+    /// the fallback has to be enabled, for all apps or by the rule for this
+    /// one, and the rule mustn't deny it.
+    fn copies(&self, app: &AXUIElement, element: &AXUIElement) -> bool {
+        // rules matching on the text see none, since it isn't known yet
+        let mut probe = self.capture(app, element, String::new(), Trigger::Copy);
+        probe.rule = self.rules.find(&probe);
+        match self.rules.get(&probe) {
+            Some(rule) => {
+                rule.action != Action::Deny && rule.copy_fallback.unwrap_or(self.fallback.enabled)
+            }
+            None => self.fallback.enabled,
+        }
+    }
+
+    /// Polls the selection of the focused element, sending an event with the
+    /// rules applied and secrets redacted and waking up the event loop
+    /// whenever it settles on a change. Returns once the app has stopped
+    /// listening.
+    pub fn run<C: Clock>(
+        &self,
+        mut stabilizer: Stabilizer<C>,
+        event_tx: mpsc::Sender<Event>,
+        proxy: EventLoopProxy,
+    ) {
+        let mut selection: Option<Capture> = None;
+        // text read by copying it, and the app it's from, kept until the
+        // next click since the app can't be asked for it again
+        let mut copied: Option<(libc::pid_t, String)> = None;
+        let mut mouse = Mouse::new();
+        // whether the last settled selection was dropped, or cleared
+        let mut dropped = false;
+        loop {
+            let button = mouse.poll();
+            if button.down || button.pressed {
+                copied = None;
+            }
+
+            let current = self
+                .focused_app()
+                .ok()
+                .and_then(|app| {
+                    let element = self.focused_element(Some(&app)).ok()?;
+                    // password fields aren't read at all
+                    if is_secure(&element) {
+                        return None;
+                    }
+                    if let Ok(text) = self.selected_text(&element) {
+                        return Some((text.to_string(), Trigger::Poll, app, element));
+                    }
+                    let pid = AXUIElementExt::pid(&*app).ok()?;
+                    // a single click only moves the caret, and some apps copy
+                    // the whole line when nothing is selected
+                    if button.released
+                        && mouse.selects()
+                        && !has_empty_selection(&element)
+                        && self.copies(&app, &element)
+                    {
+                        copied = self.copy_fallback.selected_text().map(|text| (pid, text));
+                    }
+                    let (_, text) = copied.as_ref().filter(|(from, _)| *from == pid)?;
+                    Some((text.clone(), Trigger::Copy, app, element))
+                })
+                .filter(|(text, ..)| !text.trim().is_empty());
+            let element = current.as_ref().map(|(.., element)| element.clone());
+            // the provenance is only read again once the text changes
+            selection = current.map(|(text, trigger, app, element)| match selection.take() {
+                Some(capture) if capture.text == text => capture,
+                _ => self.capture(&app, &element, text, trigger),
+            });
+
+            if let Some(event) = stabilizer.observe(selection.clone(), button.down) {
+                let event = match event {
+                    Event::SelectionChanged(capture) => self
+                        .process(capture, element.as_deref())
+                        .map(Event::SelectionChanged),
+                    // the app never saw the capture this one extends
+                    Event::SelectionExtended(mut capture) if dropped => {
+                        capture.extends_previous = false;
+                        self.process(capture, element.as_deref())
+                            .map(Event::SelectionChanged)
+                    }
+                    Event::SelectionExtended(capture) => self
+                        .process(capture, element.as_deref())
+                        .map(Event::SelectionExtended),
+                    Event::SelectionCleared => None,
+                };
+                dropped = event.is_none();
+                // a dropped capture hides whatever was shown before it
+                let event = event.unwrap_or(Event::SelectionCleared);
+                if event_tx.send(event).is_err() {
+                    return;
+                }
+                proxy.wake_up();
+            }
+
+            // polls early when a selection is due to settle, unless it's only
+            // waiting for the button to come up
+            let now = Instant::now();
+            let wake = stabilizer
+                .deadline()
+                .filter(|&deadline| deadline > now)
+                .map_or(now + POLL_INTERVAL, |deadline| {
+                    deadline.min(now + POLL_INTERVAL)
+                });
+            thread::sleep(wake - now);
+        }
+    }
+}
+
+/// The left mouse button as of a poll.
+#[derive(Clone, Copy, Debug)]
+struct Button {
+    down: bool,
+    /// Whether it was pressed since the last poll.
+    pressed: bool,
+    /// Whether it was let go since the last poll.
+    released: bool,
+}
+
+/// Follows the left mouse button across polls. Presses and drags are
+/// counted by the window server, so gestures that start and end between two
+/// polls aren't missed.
+struct Mouse {
+    presses: u32,
+    drags: u32,
+    was_down: bool,
+    last_press: Instant,
+    /// Presses in a row, each within the double-click interval of the last.
+    clicks: u32,
+    /// Whether the pointer moved with the button down since the last press.
+    dragged: bool,
+}
+
+impl Mouse {
+    fn new() -> Self {
+        Self {
+            presses: event_count(CGEventType::LeftMouseDown),
+            drags: event_count(CGEventType::LeftMouseDragged),
+            was_down: false,
+            last_press: Instant::now(),
+            clicks: 0,
+            dragged: false,
+        }
+    }
+
+    fn poll(&mut self) -> Button {
+        let down = unsafe { NSEvent::pressedMouseButtons() } & 1 != 0;
+        let presses = event_count(CGEventType::LeftMouseDown);
+        let drags = event_count(CGEventType::LeftMouseDragged);
+
+        let pressed = presses.wrapping_sub(self.presses);
+        if pressed > 0 {
+            let interval = Duration::from_secs_f64(unsafe { NSEvent::doubleClickInterval() });
+            if self.last_press.elapsed() > interval {
+                self.clicks = 0;
+            }
+            self.clicks += pressed;
+            self.last_press = Instant::now();
+            self.dragged = false;
+        }
+        if drags != self.drags {
+            self.dragged = true;
+        }
+        (self.presses, self.drags) = (presses, drags);
+
+        // a click can start and end between two polls
+        let released = !down && (self.was_down || pressed > 0);
+        self.was_down = down;
+        Button {
+            down,
+            pressed: pressed > 0,
+            released,
+        }
+    }
+
+    /// Whether the last gesture can have selected something: a drag or a
+    /// double or triple click.
+    fn selects(&self) -> bool {
+        self.dragged || self.clicks > 1
     }
 }
+
+/// How many events of `kind` there have been since the user logged in.
+fn event_count(kind: CGEventType) -> u32 {
+    CGEventSource::counter_for_event_type(CGEventSourceStateID::CombinedSessionState, kind)
+}
+
+fn is_secure(element: &AXUIElement) -> bool {
+    [attribute::kAXRoleAttribute, attribute::kAXSubroleAttribute]
+        .into_iter()
+        .any(|attribute| string_attribute(element, attribute).as_deref() == Some(SECURE_TEXT_FIELD))
+}
+
+/// Whether `element` reports a selection that's empty, `false` if it doesn't
+/// report one at all.
+fn has_empty_selection(element: &AXUIElement) -> bool {
+    element
+        .attribute_value(&CFString::from_static_str(
+            attribute::kAXSelectedTextRangeAttribute,
+        ))
+        .ok()
+        .and_then(|range| range.downcast::<AXValue>().ok())
+        .and_then(|range| range.get_value::<CFRange>())
+        .is_some_and(|range| range.length == 0)
+}
+
+/// The element the selection starts in, for apps that expose text markers.
+/// In a browser that's the text the selection begins with, inside any link
+/// around it, while the focused element is the web area or a text field.
+fn selection_start(element: &AXUIElement) -> Option<CFRetained<AXUIElement>> {
+    let range = element
+        .attribute_value(&CFString::from_static_str("AXSelectedTextMarkerRange"))
+        .ok()?;
+    let start = element
+        .parameterized_attribute_value(
+            &CFString::from_static_str("AXStartTextMarkerForTextMarkerRange"),
+            &range,
+        )
+        .ok()?;
+    element
+        .parameterized_attribute_value(
+            &CFString::from_static_str("AXUIElementForTextMarker"),
+            &start,
+        )
+        .ok()?
+        .downcast::<AXUIElement>()
+        .ok()
+}
+
+/// Finds the web page `element` is in by walking up to its `AXWebArea`,
+/// noting the target of the first link on the way.
+fn page(element: &AXUIElement) -> Option<Page> {
+    let mut link = None;
+    let mut current = element.retain();
+    for _ in 0..MAX_DEPTH {
+        match string_attribute(&current, attribute::kAXRoleAttribute).as_deref() {
+            Some(WEB_AREA) => {
+                return Some(Page {
+                    url: url_attribute(&current, attribute::kAXURLAttribute),
+                    title: string_attribute(&current, attribute::kAXTitleAttribute),
+                    link,
+                });
+            }
+            Some(LINK) if link.is_none() => {
+                link = url_attribute(&current, attribute::kAXURLAttribute);
+            }
+            _ => {}
+        }
+        current = current
+            .attribute_value(&CFString::from_static_str(attribute::kAXParentAttribute))
+            .ok()?
+            .downcast::<AXUIElement>()
+            .ok()?;
+    }
+    None
+}
+
+/// Reads a URL attribute as a string, `None` if it's missing or not a URL.
+fn url_attribute(element: &AXUIElement, attribute: &'static str) -> Option<String> {
+    element
+        .attribute_value(&CFString::from_static_str(attribute))
+        .ok()?
+        .downcast::<CFURL>()
+        .ok()
+        .map(|url| url.string().to_string())
+}
+
+/// Reads a string attribute, `None` if it's missing or not a string.
+fn string_attribute(element: &AXUIElement, attribute: &'static str) -> Option<String> {
+    element
+        .attribute_value(&CFString::from_static_str(attribute))
+        .ok()?
+        .downcast::<CFString>()
+        .ok()
+        .map(|value| value.to_string())
+}
@@ -1 +1,17 @@
-pub enum Event {}
+use crate::capture::Capture;
+use crate::card::Card;
+
+/// What the controller and the context providers report to the app.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    SelectionChanged(Capture),
+    /// The last selection was extended on either end.
+    SelectionExtended(Capture),
+    SelectionCleared,
+    /// Cards from context providers for the selection of `generation`, best
+    /// first.
+    Cards {
+        generation: u64,
+        cards: Vec<Card>,
+    },
+}
@@ -0,0 +1,212 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use glyphon::{Fallback, FontSystem, PlatformFallback, fontdb};
+#[cfg(target_os = "macos")]
+use objc2_foundation::NSLocale;
+use serde::Deserialize;
+use unicode_script::Script;
+
+/// Fonts from `assets/fonts`, embedded by the build script.
+const BUNDLED: &[&[u8]] = include!(concat!(env!("OUT_DIR"), "/bundled_fonts.rs"));
+
+/// Which fonts the overlay uses, on top of the system and bundled ones.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct FontConfig {
+    /// Extra font files or directories to load.
+    pub paths: Vec<PathBuf>,
+    /// Family the `sans-serif` generic name resolves to.
+    pub primary: String,
+    /// Family the `monospace` generic name resolves to.
+    pub monospace: String,
+    /// Family tried first for characters no other font has.
+    pub emoji: String,
+    pub fallback: FallbackChains,
+}
+
+impl Default for FontConfig {
+    fn default() -> Self {
+        Self {
+            paths: Vec::new(),
+            primary: "Helvetica Neue".to_owned(),
+            monospace: "Menlo".to_owned(),
+            emoji: "Apple Color Emoji".to_owned(),
+            fallback: FallbackChains::default(),
+        }
+    }
+}
+
+/// Families tried in order for text the primary family can't show. Empty
+/// chains leave it to the platform defaults.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct FallbackChains {
+    /// Tried for any script, after the script specific ones.
+    pub common: Vec<String>,
+    /// Chinese, Japanese and Korean.
+    pub cjk: Vec<String>,
+    pub arabic: Vec<String>,
+}
+
+/// Creates font systems set up according to a [`FontConfig`].
+pub struct Fonts {
+    /// Loaded once and shared by the font systems of all windows.
+    db: fontdb::Database,
+    fallback: ChainFallback,
+}
+
+impl Fonts {
+    pub fn new(config: FontConfig) -> Self {
+        let mut db = fontdb::Database::new();
+        db.load_system_fonts();
+        Self::with_database(config, db)
+    }
+
+    /// Uses the fonts in `db` along with the bundled and configured ones.
+    fn with_database(config: FontConfig, mut db: fontdb::Database) -> Self {
+        for font in BUNDLED {
+            db.load_font_source(fontdb::Source::Binary(Arc::new(*font)));
+        }
+        for path in &config.paths {
+            if path.is_dir() {
+                db.load_fonts_dir(path);
+            } else if let Err(err) = db.load_font_file(path) {
+                eprintln!("failed to load font {}: {err}", path.display());
+            }
+        }
+        db.set_sans_serif_family(&config.primary);
+        db.set_monospace_family(&config.monospace);
+
+        // the fallback lists have to be 'static, they are only built once
+        let common = [config.emoji.clone()]
+            .into_iter()
+            .chain(config.fallback.common.iter().cloned());
+        let fallback = ChainFallback {
+            common: leak(common),
+            cjk: leak(config.fallback.cjk.iter().cloned()),
+            arabic: leak(config.fallback.arabic.iter().cloned()),
+        };
+        Self { db, fallback }
+    }
+
+    pub fn font_system(&self) -> FontSystem {
+        let locale = sys_locale().unwrap_or_else(|| "en-US".to_owned());
+        FontSystem::new_with_locale_and_db_and_fallback(locale, self.db.clone(), self.fallback)
+    }
+}
+
+/// Tries the configured chains before the platform's.
+#[derive(Clone, Copy, Debug)]
+struct ChainFallback {
+    common: &'static [&'static str],
+    cjk: &'static [&'static str],
+    arabic: &'static [&'static str],
+}
+
+impl Fallback for ChainFallback {
+    fn common_fallback(&self) -> &[&'static str] {
+        if self.common.is_empty() {
+            PlatformFallback.common_fallback()
+        } else {
+            self.common
+        }
+    }
+
+    fn forbidden_fallback(&self) -> &[&'static str] {
+        PlatformFallback.forbidden_fallback()
+    }
+
+    fn script_fallback(&self, script: Script, locale: &str) -> &[&'static str] {
+        let chain = match script {
+            Script::Han | Script::Hiragana | Script::Katakana | Script::Hangul => self.cjk,
+            Script::Arabic => self.arabic,
+            _ => &[],
+        };
+        if chain.is_empty() {
+            PlatformFallback.script_fallback(script, locale)
+        } else {
+            chain
+        }
+    }
+}
+
+fn leak(names: impl Iterator<Item = String>) -> &'static [&'static str] {
+    let names: Vec<&'static str> = names
+        .filter(|name| !name.is_empty())
+        .map(|name| &*name.leak())
+        .collect();
+    names.leak()
+}
+
+/// The user's preferred language, like `en-US`. Apps started from the Finder
+/// don't get a `LANG`.
+#[cfg(target_os = "macos")]
+fn sys_locale() -> Option<String> {
+    let languages = unsafe { NSLocale::preferredLanguages() };
+    unsafe { languages.firstObject() }.map(|language| language.to_string())
+}
+
+/// The user's locale, as the `LANG` environment variable has it.
+#[cfg(not(target_os = "macos"))]
+fn sys_locale() -> Option<String> {
+    let lang = std::env::var("LANG").ok()?;
+    let locale = lang.split('.').next()?.replace('_', "-");
+    (!locale.is_empty() && locale != "C").then_some(locale)
+}
+
+/// A font system with only the bundled fonts, so what's shaped doesn't depend
+/// on the fonts installed.
+#[cfg(test)]
+pub fn bundled_font_system() -> FontSystem {
+    let config = FontConfig {
+        primary: "Inter".to_owned(),
+        monospace: "Fira Mono".to_owned(),
+        emoji: String::new(),
+        ..FontConfig::default()
+    };
+    Fonts::with_database(config, fontdb::Database::new()).font_system()
+}
+
+#[cfg(test)]
+mod tests {
+    use glyphon::{Attrs, Buffer, Family, Metrics, Shaping};
+
+    use super::*;
+
+    /// The clusters of `text` shaped as the notdef glyph.
+    fn notdefs(text: &str, family: Family) -> Vec<String> {
+        let mut font_system = bundled_font_system();
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(14.0, 20.0));
+        let attrs = Attrs::new().family(family);
+        buffer.set_text(&mut font_system, text, &attrs, Shaping::Advanced);
+        buffer.shape_until_scroll(&mut font_system, false);
+        buffer
+            .layout_runs()
+            .flat_map(|run| {
+                run.glyphs
+                    .iter()
+                    .filter(|glyph| glyph.glyph_id == 0)
+                    .map(|glyph| run.text[glyph.start..glyph.end].to_owned())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shapes_mixed_scripts() {
+        let text = "Hello · Καλημέρα · Привет · مرحبا بالعالم · שלום עולם";
+        assert_eq!(notdefs(text, Family::SansSerif), Vec::<String>::new());
+    }
+
+    #[test]
+    fn shapes_mixed_scripts_across_lines() {
+        let text = "café naïve résumé\nالسلام عليكم 123\nשלום, world!";
+        assert_eq!(notdefs(text, Family::SansSerif), Vec::<String>::new());
+    }
+
+    #[test]
+    fn shapes_code_in_the_monospace_family() {
+        let text = "let café = \"naïve\"; // Привет, مرحبا";
+        assert_eq!(notdefs(text, Family::Monospace), Vec::<String>::new());
+    }
+}
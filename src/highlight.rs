@@ -0,0 +1,420 @@
+use std::ops::Range;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::capture::Capture;
+
+/// Languages the overlay can highlight.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    Rust,
+    Python,
+    JavaScript,
+    Go,
+    C,
+    Swift,
+    Shell,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Token {
+    Plain,
+    Keyword,
+    Type,
+    String,
+    Number,
+    Comment,
+}
+
+/// Apps whose selections are code, and the language to assume when the
+/// content doesn't say.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+const CODE_APPS: &[(&str, Option<Language>)] = &[
+    ("com.apple.dt.Xcode", Some(Language::Swift)),
+    ("com.apple.Terminal", Some(Language::Shell)),
+    ("com.googlecode.iterm2", Some(Language::Shell)),
+    ("com.mitchellh.ghostty", Some(Language::Shell)),
+    ("net.kovidgoyal.kitty", Some(Language::Shell)),
+    ("dev.warp.Warp-Stable", Some(Language::Shell)),
+    ("com.microsoft.VSCode", None),
+    ("com.todesktop.230313mzl4w4u92", None),
+    ("dev.zed.Zed", None),
+    ("com.sublimetext.4", None),
+    ("com.panic.Nova", None),
+];
+
+impl Language {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Rust => "Rust",
+            Self::Python => "Python",
+            Self::JavaScript => "JavaScript",
+            Self::Go => "Go",
+            Self::C => "C",
+            Self::Swift => "Swift",
+            Self::Shell => "Shell",
+        }
+    }
+
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        Some(match extension.to_ascii_lowercase().as_str() {
+            "rs" => Self::Rust,
+            "py" | "pyi" => Self::Python,
+            "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => Self::JavaScript,
+            "go" => Self::Go,
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "m" | "mm" | "java" | "kt" | "cs" => Self::C,
+            "swift" => Self::Swift,
+            "sh" | "bash" | "zsh" | "fish" => Self::Shell,
+            _ => return None,
+        })
+    }
+
+    /// Guesses the language of `text` from telltale keywords.
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    pub fn guess(text: &str) -> Option<Self> {
+        const TELLTALES: &[(Language, &[&str])] = &[
+            (
+                Language::Rust,
+                &["fn ", "let mut ", "impl ", "pub fn", "use std::", "-> Self"],
+            ),
+            (Language::Go, &["func ", "package ", ":= ", "go func"]),
+            (
+                Language::Swift,
+                &["func ", "guard ", "var ", "import SwiftUI", "let "],
+            ),
+            (
+                Language::Python,
+                &["def ", "elif ", "import ", "self.", "None"],
+            ),
+            (
+                Language::JavaScript,
+                &["function ", "const ", "=> ", "===", "console."],
+            ),
+            (Language::C, &["#include", "int ", "void ", "->", "NULL"]),
+            (
+                Language::Shell,
+                &["#!/bin/", "$ ", "echo ", "fi\n", "| grep "],
+            ),
+        ];
+        TELLTALES
+            .iter()
+            .map(|(language, telltales)| {
+                let count = telltales.iter().filter(|t| text.contains(**t)).count();
+                (count, *language)
+            })
+            .filter(|(count, _)| *count >= 2)
+            .max_by_key(|(count, _)| *count)
+            .map(|(_, language)| language)
+    }
+
+    /// Whitespace-separated keywords.
+    fn keywords(self) -> &'static str {
+        match self {
+            Self::Rust => {
+                "as async await break const continue crate dyn else enum extern false fn for if \
+                 impl in let loop match mod move mut pub ref return self Self static struct super \
+                 trait true type unsafe use where while"
+            }
+            Self::Python => {
+                "and as assert async await break class continue def del elif else except False \
+                 finally for from global if import in is lambda None nonlocal not or pass raise \
+                 return self True try while with yield"
+            }
+            Self::JavaScript => {
+                "async await break case catch class const continue default delete do else export \
+                 extends false finally for from function if import in instanceof interface let \
+                 new null of return static switch this throw true try type typeof undefined var \
+                 void while yield"
+            }
+            Self::Go => {
+                "break case chan const continue default defer else fallthrough false for func go \
+                 goto if import interface map nil package range return select struct switch true \
+                 type var"
+            }
+            Self::C => {
+                "auto bool break case char class const continue default do double else enum \
+                 extern false final float for if import int long new nullptr NULL private \
+                 protected public return short signed sizeof static struct switch template this \
+                 true typedef union unsigned void while"
+            }
+            Self::Swift => {
+                "as async await break case catch class continue default defer do else enum \
+                 extension false fileprivate for func guard if import in init let nil private \
+                 protocol public return self Self static struct switch throw throws true try var \
+                 where while"
+            }
+            Self::Shell => {
+                "case do done elif else esac export fi for function if in local return then until \
+                 while"
+            }
+        }
+    }
+
+    fn line_comment(self) -> &'static str {
+        match self {
+            Self::Python | Self::Shell => "#",
+            _ => "//",
+        }
+    }
+
+    fn block_comment(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Self::Python | Self::Shell => None,
+            _ => Some(("/*", "*/")),
+        }
+    }
+
+    fn quotes(self) -> &'static [char] {
+        match self {
+            // single quotes are lifetimes as often as they are characters
+            Self::Rust => &['"'],
+            Self::JavaScript | Self::Go => &['"', '\'', '`'],
+            _ => &['"', '\''],
+        }
+    }
+}
+
+/// Decides whether `capture` is code, and in which language: by the
+/// extension of the document it's from, then by the app it's from, and
+/// failing both by its content alone.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub fn detect(capture: &Capture) -> Option<Language> {
+    let text = &capture.text;
+    let from_document = capture
+        .document
+        .as_deref()
+        .and_then(|document| Path::new(document).extension()?.to_str())
+        .and_then(Language::from_extension);
+    if from_document.is_some() {
+        return from_document;
+    }
+
+    let app = capture
+        .bundle_id
+        .as_deref()
+        .and_then(|bundle_id| CODE_APPS.iter().find(|(id, _)| *id == bundle_id));
+    match app {
+        Some((_, fallback)) => Language::guess(text).or(*fallback),
+        None => Language::guess(text),
+    }
+}
+
+/// Splits `text` into tokens, returning the byte ranges of everything that
+/// isn't plain.
+pub fn highlight(language: Language, text: &str) -> Vec<(Range<usize>, Token)> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        let start = text.len() - rest.len();
+        let (len, token) = if rest.starts_with(language.line_comment()) {
+            (rest.find('\n').unwrap_or(rest.len()), Token::Comment)
+        } else if let Some((open, close)) = language.block_comment()
+            && rest.starts_with(open)
+        {
+            let len = rest[open.len()..]
+                .find(close)
+                .map_or(rest.len(), |end| open.len() + end + close.len());
+            (len, Token::Comment)
+        } else if language.quotes().contains(&c) {
+            (string_len(rest, c), Token::String)
+        } else if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len());
+            (len, Token::Number)
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+            let token = if language.keywords().split_whitespace().any(|k| k == word) {
+                Token::Keyword
+            } else if word.starts_with(char::is_uppercase) && word.chars().any(char::is_lowercase) {
+                Token::Type
+            } else {
+                Token::Plain
+            };
+            (len, token)
+        } else {
+            (c.len_utf8(), Token::Plain)
+        };
+
+        if token != Token::Plain {
+            tokens.push((start..start + len, token));
+        }
+        rest = &rest[len..];
+    }
+    tokens
+}
+
+/// Length of the string literal at the start of `text`, up to the closing
+/// quote or the end of the line if there is none.
+fn string_len(text: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (index, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '\n' if quote != '`' => return index,
+            c if c == quote => return index + c.len_utf8(),
+            _ => {}
+        }
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Trigger;
+    use crate::theme::Theme;
+
+    fn capture(text: &str, bundle_id: Option<&str>, document: Option<&str>) -> Capture {
+        Capture {
+            bundle_id: bundle_id.map(str::to_owned),
+            document: document.map(str::to_owned),
+            ..Capture::new(text.to_owned(), Trigger::Poll)
+        }
+    }
+
+    /// The text of every highlighted token, with its kind.
+    fn tokens(language: Language, text: &str) -> Vec<(&str, Token)> {
+        highlight(language, text)
+            .into_iter()
+            .map(|(range, token)| (&text[range], token))
+            .collect()
+    }
+
+    #[test]
+    fn detects_the_language_of_the_document_first() {
+        let rust = "fn main() {\n    let mut x = 1;\n}";
+        assert_eq!(
+            detect(&capture(rust, None, Some("/src/main.py"))),
+            Some(Language::Python)
+        );
+        assert_eq!(
+            detect(&capture("x", None, Some("/src/App.TSX"))),
+            Some(Language::JavaScript)
+        );
+        assert_eq!(
+            detect(&capture(rust, None, Some("/notes/README.md"))),
+            Some(Language::Rust)
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_language_of_the_app() {
+        assert_eq!(
+            detect(&capture("ls -la", Some("com.apple.Terminal"), None)),
+            Some(Language::Shell)
+        );
+        // the content wins where it's telling
+        let python = "def f(self):\n    return None";
+        assert_eq!(
+            detect(&capture(python, Some("com.apple.Terminal"), None)),
+            Some(Language::Python)
+        );
+        assert_eq!(
+            detect(&capture("x = 1", Some("com.microsoft.VSCode"), None)),
+            None
+        );
+    }
+
+    #[test]
+    fn guesses_from_content_alone() {
+        assert_eq!(
+            Language::guess("package main\n\nfunc main() {\n\tx := 1\n}"),
+            Some(Language::Go)
+        );
+        assert_eq!(
+            Language::guess("#include <stdio.h>\nint main(void) { return 0; }"),
+            Some(Language::C)
+        );
+        // prose with a single telltale isn't code
+        assert_eq!(Language::guess("Let me know if there's None left."), None);
+        assert_eq!(
+            detect(&capture(
+                "The quick brown fox.",
+                Some("com.apple.Safari"),
+                None
+            )),
+            None
+        );
+    }
+
+    #[test]
+    fn highlights_keywords_types_numbers_and_strings() {
+        assert_eq!(
+            tokens(
+                Language::Rust,
+                "let x: Vec<u8> = vec![0x1f, 2.5]; \"a \\\" b\""
+            ),
+            [
+                ("let", Token::Keyword),
+                ("Vec", Token::Type),
+                ("0x1f", Token::Number),
+                ("2.5", Token::Number),
+                ("\"a \\\" b\"", Token::String),
+            ]
+        );
+        // all-caps words are constants rather than types
+        assert_eq!(
+            tokens(Language::C, "return NULL + MAX;"),
+            [("return", Token::Keyword), ("NULL", Token::Keyword),]
+        );
+    }
+
+    #[test]
+    fn highlights_comments() {
+        assert_eq!(
+            tokens(Language::Go, "x := 1 // one\n/* two\nlines */ y"),
+            [
+                ("1", Token::Number),
+                ("// one", Token::Comment),
+                ("/* two\nlines */", Token::Comment),
+            ]
+        );
+        assert_eq!(
+            tokens(Language::Shell, "echo hi # not /* this */"),
+            [("# not /* this */", Token::Comment)]
+        );
+    }
+
+    #[test]
+    fn ends_unterminated_strings_at_the_line() {
+        assert_eq!(
+            tokens(Language::Python, "x = 'open\ny = 1"),
+            [("'open", Token::String), ("1", Token::Number)]
+        );
+        // template literals span lines
+        assert_eq!(
+            tokens(Language::JavaScript, "`a\nb` + 1"),
+            [("`a\nb`", Token::String), ("1", Token::Number)]
+        );
+        // Rust lifetimes aren't strings
+        assert_eq!(tokens(Language::Rust, "&'a str"), []);
+    }
+
+    #[test]
+    fn colors_tokens_from_the_theme() {
+        let theme = Theme::dark();
+        let colors: Vec<_> = highlight(Language::Rust, "fn f() -> Self { 1 } // done")
+            .into_iter()
+            .map(|(_, token)| theme.token_color(token))
+            .collect();
+        assert_eq!(
+            colors,
+            [
+                theme.syntax.keyword,
+                theme.syntax.keyword,
+                theme.syntax.number,
+                theme.muted,
+            ]
+        );
+        assert_eq!(theme.token_color(Token::Plain), theme.foreground);
+    }
+}
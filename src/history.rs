@@ -0,0 +1,451 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use rusqlite::types::Value;
+use rusqlite::{Connection, params, params_from_iter};
+use serde::Deserialize;
+
+use crate::capture::Capture;
+
+/// Schema changes, applied in order. `PRAGMA user_version` holds how many
+/// have been applied, so existing entries must never change.
+const MIGRATIONS: &[&str] = &[include_str!("../migrations/history/001_captures.sql")];
+
+/// How much history is kept.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default)]
+pub struct Retention {
+    /// Captures older than this are deleted.
+    pub max_age_days: u64,
+    /// Only this many of the newest captures are kept.
+    pub max_captures: usize,
+    /// The oldest captures are deleted while the database takes up more than
+    /// this.
+    pub max_megabytes: u64,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            max_age_days: 90,
+            max_captures: 50_000,
+            max_megabytes: 256,
+        }
+    }
+}
+
+impl Retention {
+    fn max_age(&self) -> Duration {
+        Duration::from_secs(self.max_age_days * 24 * 60 * 60)
+    }
+
+    fn max_bytes(&self) -> u64 {
+        self.max_megabytes * 1024 * 1024
+    }
+}
+
+/// Which captures to look up. Unset filters match everything.
+#[derive(Clone, Debug, Default)]
+pub struct Query {
+    /// Words that have to appear in the text or its metadata, matched as
+    /// prefixes.
+    pub text: Option<String>,
+    /// Bundle identifier or name of the app.
+    pub app: Option<String>,
+    pub since: Option<SystemTime>,
+    pub until: Option<SystemTime>,
+    /// Most captures to return, newest first. Zero means no limit.
+    pub limit: usize,
+}
+
+/// A capture from the history.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub id: i64,
+    pub capture: Capture,
+}
+
+/// Every capture taken, searchable by text and metadata.
+pub struct History {
+    connection: Connection,
+    /// Id of the capture recorded last, which is replaced by a selection
+    /// extending it.
+    last: Option<i64>,
+}
+
+impl History {
+    pub fn path() -> Option<PathBuf> {
+        Some(
+            dirs::data_dir()?
+                .join(env!("CARGO_PKG_NAME"))
+                .join("history.sqlite3"),
+        )
+    }
+
+    /// Opens the history at `path`, creating or migrating it as needed.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        let connection =
+            Connection::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        Self::new(connection)
+    }
+
+    fn new(connection: Connection) -> anyhow::Result<Self> {
+        connection
+            .pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))
+            .context("failed to enable write-ahead logging")?;
+        let mut history = Self {
+            connection,
+            last: None,
+        };
+        history.migrate()?;
+        Ok(history)
+    }
+
+    /// Schema version of the database, the number of migrations applied.
+    fn version(&self) -> anyhow::Result<usize> {
+        let version: i64 = self
+            .connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .context("failed to read schema version")?;
+        Ok(version as usize)
+    }
+
+    fn migrate(&mut self) -> anyhow::Result<()> {
+        let version = self.version()?;
+        anyhow::ensure!(
+            version <= MIGRATIONS.len(),
+            "history schema version {version} is newer than this build supports"
+        );
+
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let transaction = self.connection.transaction()?;
+            transaction
+                .execute_batch(migration)
+                .with_context(|| format!("failed to migrate history to version {}", index + 1))?;
+            transaction.pragma_update(None, "user_version", index as i64 + 1)?;
+            transaction.commit()?;
+        }
+        Ok(())
+    }
+
+    pub fn insert(&self, capture: &Capture) -> anyhow::Result<i64> {
+        let json = serde_json::to_string(capture).context("failed to serialize capture")?;
+        self.connection
+            .execute(
+                "INSERT INTO captures
+                    (timestamp, text, bundle_id, app_name, window_title, document, capture)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    millis(capture.timestamp),
+                    capture.text,
+                    capture.bundle_id,
+                    capture.app_name,
+                    capture.window_title,
+                    capture.document,
+                    json,
+                ],
+            )
+            .context("failed to insert capture")?;
+        Ok(self.connection.last_insert_rowid())
+    }
+
+    /// Records `capture`, replacing the capture recorded last if it extends
+    /// that one, and returns its id.
+    pub fn record(&mut self, capture: &Capture) -> anyhow::Result<i64> {
+        let recorded = match self.last.take().filter(|_| capture.extends_previous) {
+            Some(id) => self.replace(id, capture),
+            None => self.insert(capture),
+        };
+        self.last = recorded.as_ref().ok().copied();
+        recorded
+    }
+
+    /// Keeps the next capture from replacing the last one, once the
+    /// selection is cleared or wasn't recorded.
+    pub fn forget_last(&mut self) {
+        self.last = None;
+    }
+
+    /// Replaces the capture `id` with `capture`, returning its new id.
+    pub fn replace(&self, id: i64, capture: &Capture) -> anyhow::Result<i64> {
+        self.connection
+            .execute("DELETE FROM captures WHERE id = ?1", params![id])
+            .context("failed to remove capture")?;
+        self.insert(capture)
+    }
+
+    /// Returns the captures matching `query`, newest first.
+    pub fn query(&self, query: &Query) -> anyhow::Result<Vec<Entry>> {
+        let mut sql = "SELECT captures.id, captures.capture FROM captures".to_owned();
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+
+        if let Some(text) = query.text.as_deref().and_then(match_expression) {
+            sql.push_str(" JOIN captures_fts ON captures_fts.rowid = captures.id");
+            conditions.push("captures_fts MATCH ?");
+            values.push(Value::Text(text));
+        }
+        if let Some(app) = &query.app {
+            conditions.push("(captures.bundle_id = ? OR captures.app_name = ? COLLATE NOCASE)");
+            values.push(Value::Text(app.clone()));
+            values.push(Value::Text(app.clone()));
+        }
+        if let Some(since) = query.since {
+            conditions.push("captures.timestamp >= ?");
+            values.push(Value::Integer(millis(since)));
+        }
+        if let Some(until) = query.until {
+            conditions.push("captures.timestamp < ?");
+            values.push(Value::Integer(millis(until)));
+        }
+
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(" ORDER BY captures.timestamp DESC, captures.id DESC");
+        if query.limit > 0 {
+            sql.push_str(" LIMIT ?");
+            values.push(Value::Integer(query.limit as i64));
+        }
+
+        let mut statement = self
+            .connection
+            .prepare(&sql)
+            .context("failed to query history")?;
+        let rows = statement
+            .query_map(params_from_iter(values), |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })
+            .context("failed to query history")?;
+        rows.map(|row| {
+            let (id, json) = row.context("failed to read capture")?;
+            entry(id, &json)
+        })
+        .collect()
+    }
+
+    /// Deletes the captures `retention` doesn't keep, returning how many.
+    pub fn prune(&self, retention: &Retention) -> anyhow::Result<usize> {
+        let cutoff = SystemTime::now()
+            .checked_sub(retention.max_age())
+            .unwrap_or(UNIX_EPOCH);
+        let expired = self
+            .connection
+            .execute(
+                "DELETE FROM captures WHERE timestamp < ?1",
+                [millis(cutoff)],
+            )
+            .context("failed to delete old captures")?;
+        let excess = self
+            .connection
+            .execute(
+                "DELETE FROM captures WHERE id NOT IN (
+                    SELECT id FROM captures ORDER BY timestamp DESC, id DESC LIMIT ?1
+                )",
+                [retention.max_captures as i64],
+            )
+            .context("failed to delete excess captures")?;
+        let oversized = self.prune_to_size(retention.max_bytes())?;
+        Ok(expired + excess + oversized)
+    }
+
+    /// Deletes the oldest captures in proportion to how far the database is
+    /// over `max_bytes`, assuming they're of about the same size.
+    fn prune_to_size(&self, max_bytes: u64) -> anyhow::Result<usize> {
+        let size = self.size()?;
+        if size <= max_bytes {
+            return Ok(0);
+        }
+        let count: i64 = self
+            .connection
+            .query_row("SELECT COUNT(*) FROM captures", [], |row| row.get(0))
+            .context("failed to count captures")?;
+        let excess = (count as f64 * (1.0 - max_bytes as f64 / size as f64)).ceil() as i64;
+        self.connection
+            .execute(
+                "DELETE FROM captures WHERE id IN (
+                    SELECT id FROM captures ORDER BY timestamp, id LIMIT ?1
+                )",
+                [excess],
+            )
+            .context("failed to delete captures over the size limit")
+    }
+
+    /// Bytes the database takes up, not counting pages freed by deletes that
+    /// are reused before the file grows.
+    fn size(&self) -> anyhow::Result<u64> {
+        let pragma = |name: &str| {
+            self.connection
+                .pragma_query_value(None, name, |row| row.get::<_, i64>(0))
+                .with_context(|| format!("failed to read {name}"))
+        };
+        let pages = pragma("page_count")? - pragma("freelist_count")?;
+        Ok((pages * pragma("page_size")?) as u64)
+    }
+}
+
+fn entry(id: i64, json: &str) -> anyhow::Result<Entry> {
+    let capture =
+        serde_json::from_str(json).with_context(|| format!("failed to parse capture {id}"))?;
+    Ok(Entry { id, capture })
+}
+
+/// Turns free text into an FTS5 expression matching every word as a prefix,
+/// quoting them so operators and punctuation are taken literally.
+fn match_expression(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+fn millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Trigger;
+
+    fn capture(text: &str) -> Capture {
+        Capture {
+            timestamp: SystemTime::now(),
+            ..Capture::new(text.to_owned(), Trigger::Poll)
+        }
+    }
+
+    fn count(history: &History) -> usize {
+        history.query(&Query::default()).unwrap().len()
+    }
+
+    /// An in-memory database with the first `version` migrations applied.
+    fn database(version: usize) -> Connection {
+        let connection = Connection::open_in_memory().unwrap();
+        for migration in &MIGRATIONS[..version] {
+            connection.execute_batch(migration).unwrap();
+        }
+        connection
+            .pragma_update(None, "user_version", version as i64)
+            .unwrap();
+        connection
+    }
+
+    #[test]
+    fn replaces_only_the_capture_recorded_last() {
+        let mut history = History::new(Connection::open_in_memory().unwrap()).unwrap();
+        let extension = |text: &str| Capture {
+            extends_previous: true,
+            ..capture(text)
+        };
+        history.record(&capture("hello")).unwrap();
+        history.record(&extension("hello world")).unwrap();
+        let texts = |history: &History| {
+            let entries = history.query(&Query::default()).unwrap();
+            let mut texts: Vec<_> = entries
+                .into_iter()
+                .map(|entry| entry.capture.text)
+                .collect();
+            texts.sort();
+            texts
+        };
+        assert_eq!(texts(&history), ["hello world"]);
+
+        // the selection was cleared, or dropped before it was recorded, so
+        // what the stabilizer took for an extension of it is new
+        history.forget_last();
+        history.record(&extension("hello world!")).unwrap();
+        assert_eq!(texts(&history), ["hello world", "hello world!"]);
+    }
+
+    #[test]
+    fn creates_a_fresh_database() {
+        let history = History::new(Connection::open_in_memory().unwrap()).unwrap();
+        assert_eq!(history.version().unwrap(), MIGRATIONS.len());
+
+        let id = history.insert(&capture("hello world")).unwrap();
+        let entries = history.query(&Query {
+            text: Some("wor".to_owned()),
+            ..Query::default()
+        });
+        assert_eq!(entries.unwrap()[0].id, id);
+    }
+
+    #[test]
+    fn upgrades_from_the_previous_version() {
+        let history = History::new(database(MIGRATIONS.len() - 1)).unwrap();
+        assert_eq!(history.version().unwrap(), MIGRATIONS.len());
+        history.insert(&capture("hello")).unwrap();
+        assert_eq!(count(&history), 1);
+    }
+
+    #[test]
+    fn keeps_captures_of_an_up_to_date_database() {
+        let mut history = History::new(database(MIGRATIONS.len())).unwrap();
+        history.insert(&capture("hello")).unwrap();
+        history.migrate().unwrap();
+        assert_eq!(count(&history), 1);
+    }
+
+    #[test]
+    fn rejects_a_newer_database() {
+        let connection = database(MIGRATIONS.len());
+        connection
+            .pragma_update(None, "user_version", MIGRATIONS.len() as i64 + 1)
+            .unwrap();
+        let err = History::new(connection).err().unwrap();
+        assert!(err.to_string().contains("newer"), "{err:#}");
+    }
+
+    #[test]
+    fn prunes_old_and_excess_captures() {
+        let history = History::new(Connection::open_in_memory().unwrap()).unwrap();
+        history
+            .insert(&Capture {
+                timestamp: UNIX_EPOCH + Duration::from_secs(1),
+                ..capture("ancient")
+            })
+            .unwrap();
+        for text in ["one", "two", "three"] {
+            history.insert(&capture(text)).unwrap();
+        }
+
+        let retention = Retention {
+            max_captures: 2,
+            ..Retention::default()
+        };
+        assert_eq!(history.prune(&retention).unwrap(), 2);
+        let texts: Vec<_> = history
+            .query(&Query::default())
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.capture.text)
+            .collect();
+        assert_eq!(texts, ["three", "two"]);
+    }
+
+    #[test]
+    fn prunes_the_oldest_captures_past_the_size_limit() {
+        let history = History::new(Connection::open_in_memory().unwrap()).unwrap();
+        let text = "lorem ipsum ".repeat(400);
+        for i in 0..200 {
+            history.insert(&capture(&format!("{i} {text}"))).unwrap();
+        }
+        let size = history.size().unwrap();
+
+        assert_eq!(history.prune_to_size(size).unwrap(), 0);
+        let pruned = history.prune_to_size(size / 2).unwrap();
+        assert!((90..=110).contains(&pruned), "{pruned}");
+        let newest = &history.query(&Query::default()).unwrap()[0];
+        assert!(newest.capture.text.starts_with("199 "));
+    }
+}
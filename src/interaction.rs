@@ -0,0 +1,147 @@
+/// How an action button is drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ButtonState {
+    Normal,
+    Hovered,
+    Pressed,
+}
+
+/// Pointer and keyboard state of the action buttons in a card.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Interaction {
+    count: usize,
+    hovered: Option<usize>,
+    pressed: Option<usize>,
+    focused: Option<usize>,
+}
+
+impl Interaction {
+    /// Starts over with `count` buttons, none of them hovered or focused.
+    pub fn reset(&mut self, count: usize) {
+        *self = Self {
+            count,
+            ..Self::default()
+        };
+    }
+
+    pub fn state(&self, index: usize) -> ButtonState {
+        if self.pressed == Some(index) && self.hovered == Some(index) {
+            ButtonState::Pressed
+        } else if self.hovered == Some(index) {
+            ButtonState::Hovered
+        } else {
+            ButtonState::Normal
+        }
+    }
+
+    pub fn is_focused(&self, index: usize) -> bool {
+        self.focused == Some(index)
+    }
+
+    /// Moves the pointer over the button at `hit`, if any. Returns whether
+    /// anything needs to be redrawn.
+    pub fn pointer_moved(&mut self, hit: Option<usize>) -> bool {
+        let changed = self.hovered != hit;
+        self.hovered = hit;
+        changed
+    }
+
+    pub fn pointer_pressed(&mut self) -> bool {
+        self.pressed = self.hovered;
+        self.pressed.is_some()
+    }
+
+    /// Releases the pointer, returning the button that was clicked, which is
+    /// only the case if it was pressed and released over the same button.
+    pub fn pointer_released(&mut self) -> Option<usize> {
+        let pressed = self.pressed.take()?;
+        (self.hovered == Some(pressed)).then_some(pressed)
+    }
+
+    /// Moves the keyboard focus to the next button, wrapping around.
+    pub fn focus_next(&mut self, backwards: bool) {
+        if self.count == 0 {
+            return;
+        }
+        self.focused = Some(match (self.focused, backwards) {
+            (None, false) => 0,
+            (None, true) => self.count - 1,
+            (Some(index), false) => (index + 1) % self.count,
+            (Some(index), true) => (index + self.count - 1) % self.count,
+        });
+    }
+
+    /// The button Enter runs: the focused one, or else the first.
+    pub fn activated(&self) -> Option<usize> {
+        self.focused.or((self.count > 0).then_some(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interaction(count: usize) -> Interaction {
+        let mut interaction = Interaction::default();
+        interaction.reset(count);
+        interaction
+    }
+
+    #[test]
+    fn hovers_and_presses_buttons() {
+        let mut interaction = interaction(2);
+        assert!(interaction.pointer_moved(Some(1)));
+        assert!(!interaction.pointer_moved(Some(1)));
+        assert_eq!(interaction.state(1), ButtonState::Hovered);
+        assert_eq!(interaction.state(0), ButtonState::Normal);
+
+        assert!(interaction.pointer_pressed());
+        assert_eq!(interaction.state(1), ButtonState::Pressed);
+        // a pressed button looks pressed only while the pointer is over it
+        interaction.pointer_moved(None);
+        assert_eq!(interaction.state(1), ButtonState::Normal);
+    }
+
+    #[test]
+    fn clicks_only_when_released_over_the_pressed_button() {
+        let mut interaction = interaction(2);
+        interaction.pointer_moved(Some(0));
+        interaction.pointer_pressed();
+        assert_eq!(interaction.pointer_released(), Some(0));
+        assert_eq!(interaction.pointer_released(), None);
+
+        interaction.pointer_pressed();
+        interaction.pointer_moved(Some(1));
+        assert_eq!(interaction.pointer_released(), None);
+
+        // pressing outside any button clicks nothing
+        interaction.pointer_moved(None);
+        assert!(!interaction.pointer_pressed());
+        interaction.pointer_moved(Some(1));
+        assert_eq!(interaction.pointer_released(), None);
+    }
+
+    #[test]
+    fn moves_the_focus_around() {
+        let mut interaction = interaction(3);
+        assert_eq!(interaction.activated(), Some(0));
+        interaction.focus_next(true);
+        assert!(interaction.is_focused(2));
+        interaction.focus_next(false);
+        assert!(interaction.is_focused(0));
+        interaction.focus_next(false);
+        assert_eq!(interaction.activated(), Some(1));
+
+        // a new card starts unfocused
+        interaction.reset(3);
+        assert!(!interaction.is_focused(1));
+        assert_eq!(interaction.activated(), Some(0));
+    }
+
+    #[test]
+    fn does_nothing_without_buttons() {
+        let mut interaction = interaction(0);
+        interaction.focus_next(false);
+        assert_eq!(interaction.activated(), None);
+    }
+}
@@ -0,0 +1,421 @@
+use std::ops::Range;
+
+use glyphon::{Attrs, Buffer, FontSystem, Shaping, Wrap};
+use serde::Deserialize;
+use winit::dpi::{LogicalSize, PhysicalSize};
+
+const ELLIPSIS: char = '…';
+
+/// Bounds the overlay is fitted into, in logical pixels.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default)]
+pub struct SizeLimits {
+    pub min_width: f32,
+    pub min_height: f32,
+    pub max_width: f32,
+    pub max_height: f32,
+    pub max_lines: usize,
+}
+
+impl Default for SizeLimits {
+    fn default() -> Self {
+        Self {
+            min_width: 120.0,
+            min_height: 36.0,
+            max_width: 480.0,
+            max_height: 360.0,
+            max_lines: 8,
+        }
+    }
+}
+
+impl SizeLimits {
+    /// Shrinks the limits by `amount` on every side.
+    pub fn shrink(&self, amount: f32) -> Self {
+        Self {
+            min_width: (self.min_width - 2.0 * amount).max(0.0),
+            min_height: (self.min_height - 2.0 * amount).max(0.0),
+            max_width: (self.max_width - 2.0 * amount).max(0.0),
+            max_height: (self.max_height - 2.0 * amount).max(0.0),
+            ..*self
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Extent {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Extent {
+    pub fn clamp(self, limits: &SizeLimits) -> Self {
+        // the maximum wins over a minimum it's been shrunk below
+        Self {
+            width: self
+                .width
+                .ceil()
+                .max(limits.min_width)
+                .min(limits.max_width),
+            height: self
+                .height
+                .ceil()
+                .max(limits.min_height)
+                .min(limits.max_height),
+        }
+    }
+
+    /// Grows the extent by `amount` on every side.
+    pub fn grow(self, amount: f32) -> Self {
+        Self {
+            width: self.width + 2.0 * amount,
+            height: self.height + 2.0 * amount,
+        }
+    }
+
+    /// Physical size of a surface showing the extent at `scale_factor`,
+    /// rounded to the nearest pixel.
+    pub fn to_physical(self, scale_factor: f64) -> PhysicalSize<u32> {
+        LogicalSize::new(self.width, self.height).to_physical(scale_factor)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+/// Returns the extent of the laid out lines of `buffer`.
+pub fn measure(buffer: &Buffer) -> Extent {
+    buffer
+        .layout_runs()
+        .fold(Extent::default(), |extent, run| Extent {
+            width: extent.width.max(run.line_w),
+            height: run.line_top + run.line_height,
+        })
+}
+
+/// Text with runs that override its default attributes, like highlighted
+/// tokens.
+#[derive(Clone, Debug)]
+pub struct RichText<'a> {
+    pub text: &'a str,
+    pub attrs: Attrs<'a>,
+    /// Byte ranges into `text`, in order and not overlapping.
+    pub spans: Vec<(Range<usize>, Attrs<'a>)>,
+}
+
+impl<'a> RichText<'a> {
+    pub fn plain(text: &'a str, attrs: Attrs<'a>) -> Self {
+        Self {
+            text,
+            attrs,
+            spans: Vec::new(),
+        }
+    }
+
+    /// Sets the first `len` bytes of the text, followed by `suffix`, as the
+    /// contents of `buffer`.
+    fn set(&self, font_system: &mut FontSystem, buffer: &mut Buffer, len: usize, suffix: &str) {
+        let mut runs = Vec::with_capacity(2 * self.spans.len() + 2);
+        let mut end = 0;
+        for (range, attrs) in &self.spans {
+            let range = range.start.min(len)..range.end.min(len);
+            if range.is_empty() {
+                continue;
+            }
+            runs.push((&self.text[end..range.start], self.attrs.clone()));
+            runs.push((&self.text[range.clone()], attrs.clone()));
+            end = range.end;
+        }
+        runs.push((&self.text[end..len], self.attrs.clone()));
+        runs.push((suffix, self.attrs.clone()));
+
+        buffer.set_rich_text(
+            font_system,
+            runs.into_iter().filter(|(text, _)| !text.is_empty()),
+            &self.attrs,
+            Shaping::Advanced,
+            None,
+        );
+        buffer.shape_until_scroll(font_system, false);
+    }
+}
+
+/// What happens to text past the maximum number of lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// Cut it off with an ellipsis.
+    Ellipsize,
+    /// Keep all of it in the buffer, to be scrolled into view.
+    Scroll,
+}
+
+/// Shapes `text` into `buffer`, wrapping at the maximum width, and returns the
+/// clamped extent of the lines that are visible at once.
+pub fn layout_text(
+    font_system: &mut FontSystem,
+    buffer: &mut Buffer,
+    text: &RichText,
+    limits: &SizeLimits,
+    overflow: Overflow,
+) -> Extent {
+    let line_height = buffer.metrics().line_height;
+    let max_lines = limits
+        .max_lines
+        .min((limits.max_height / line_height) as usize)
+        .max(1);
+
+    buffer.set_wrap(font_system, Wrap::WordOrGlyph);
+    buffer.set_size(font_system, Some(limits.max_width), None);
+    text.set(font_system, buffer, text.text.len(), "");
+
+    if overflow == Overflow::Ellipsize
+        && let Some(mut kept) = truncate_to_lines(buffer, max_lines)
+    {
+        let ellipsis = ELLIPSIS.to_string();
+        loop {
+            let len = text.text[..kept].trim_end().len();
+            text.set(font_system, buffer, len, &ellipsis);
+            if buffer.layout_runs().count() <= max_lines || kept == 0 {
+                break;
+            }
+            kept = text.text[..kept]
+                .char_indices()
+                .next_back()
+                .map_or(0, |(index, _)| index);
+        }
+    }
+
+    let extent = measure(buffer);
+    Extent {
+        height: extent.height.min(max_lines as f32 * line_height),
+        ..extent
+    }
+    .clamp(limits)
+}
+
+/// Returns the length of the text that fits into the first `max_lines` layout
+/// runs, or `None` if all of it fits already.
+fn truncate_to_lines(buffer: &Buffer, max_lines: usize) -> Option<usize> {
+    if buffer.layout_runs().count() <= max_lines {
+        return None;
+    }
+
+    let last = buffer.layout_runs().nth(max_lines - 1)?;
+    let end = last.glyphs.iter().map(|glyph| glyph.end).max().unwrap_or(0);
+
+    let before: usize = buffer.lines[..last.line_i]
+        .iter()
+        .map(|line| line.text().len() + line.ending().as_str().len())
+        .sum();
+    Some(before + end)
+}
+
+#[cfg(test)]
+mod tests {
+    use glyphon::{Family, Metrics};
+
+    use super::*;
+    use crate::fonts::bundled_font_system;
+
+    const LINE_HEIGHT: f32 = 24.0;
+    const TEXT: &str = "The quick brown fox jumps over the lazy dog, and then it does so \
+        again and again until the text no longer fits on a single line of the overlay.";
+
+    fn layout(text: &str, limits: &SizeLimits, overflow: Overflow) -> (Extent, Buffer) {
+        let mut font_system = bundled_font_system();
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(20.0, LINE_HEIGHT));
+        let text = RichText::plain(text, Attrs::new().family(Family::SansSerif));
+        let extent = layout_text(&mut font_system, &mut buffer, &text, limits, overflow);
+        (extent, buffer)
+    }
+
+    /// The text of each line as laid out.
+    fn lines(buffer: &Buffer) -> Vec<String> {
+        buffer
+            .layout_runs()
+            .map(|run| match (run.glyphs.first(), run.glyphs.last()) {
+                (Some(first), Some(last)) => run.text[first.start..last.end].to_owned(),
+                _ => String::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fits_short_text_into_the_minimum_size() {
+        let limits = SizeLimits::default();
+        let (extent, _) = layout("Hi", &limits, Overflow::Ellipsize);
+        assert_eq!(
+            extent,
+            Extent {
+                width: limits.min_width,
+                height: limits.min_height,
+            }
+        );
+    }
+
+    #[test]
+    fn measures_a_single_line() {
+        let limits = SizeLimits::default();
+        let (extent, buffer) = layout("The quick brown fox", &limits, Overflow::Ellipsize);
+        assert_eq!(lines(&buffer).len(), 1);
+        assert!(extent.width > limits.min_width);
+        assert_eq!(extent.width, measure(&buffer).width.ceil());
+        assert_eq!(extent.height, limits.min_height);
+    }
+
+    #[test]
+    fn wraps_at_the_maximum_width() {
+        let limits = SizeLimits::default();
+        let (extent, buffer) = layout(TEXT, &limits, Overflow::Ellipsize);
+        let count = buffer.layout_runs().count();
+        assert!(count > 1);
+        assert!(
+            buffer
+                .layout_runs()
+                .all(|run| run.line_w <= limits.max_width)
+        );
+        assert!(extent.width <= limits.max_width);
+        assert_eq!(extent.height, count as f32 * LINE_HEIGHT);
+    }
+
+    #[test]
+    fn ellipsizes_past_the_maximum_lines() {
+        let limits = SizeLimits {
+            max_lines: 2,
+            ..SizeLimits::default()
+        };
+        let (extent, buffer) = layout(TEXT, &limits, Overflow::Ellipsize);
+        let lines = lines(&buffer);
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].ends_with(ELLIPSIS), "{lines:?}");
+        assert_eq!(extent.height, 2.0 * LINE_HEIGHT);
+    }
+
+    #[test]
+    fn keeps_everything_past_the_maximum_lines_for_scrolling() {
+        let limits = SizeLimits {
+            max_lines: 2,
+            ..SizeLimits::default()
+        };
+        let (extent, buffer) = layout(TEXT, &limits, Overflow::Scroll);
+        let lines = lines(&buffer);
+        assert!(lines.len() > 2);
+        assert!(!lines.iter().any(|line| line.contains(ELLIPSIS)));
+        assert_eq!(extent.height, 2.0 * LINE_HEIGHT);
+    }
+
+    #[test]
+    fn fits_as_many_lines_as_the_maximum_height_allows() {
+        let limits = SizeLimits {
+            max_height: 2.5 * LINE_HEIGHT,
+            ..SizeLimits::default()
+        };
+        let (extent, buffer) = layout(TEXT, &limits, Overflow::Ellipsize);
+        assert_eq!(buffer.layout_runs().count(), 2);
+        assert_eq!(extent.height, 2.0 * LINE_HEIGHT);
+    }
+
+    #[test]
+    fn keeps_ellipsized_lines_within_the_maximum_width() {
+        let limits = SizeLimits {
+            max_width: 200.0,
+            max_lines: 1,
+            ..SizeLimits::default()
+        };
+        let (extent, buffer) = layout(TEXT, &limits, Overflow::Ellipsize);
+        assert_eq!(lines(&buffer).len(), 1);
+        assert!(extent.width <= 200.0);
+    }
+
+    #[test]
+    fn clamps_and_rounds_up_extents() {
+        let limits = SizeLimits::default();
+        let clamp = |width, height| Extent { width, height }.clamp(&limits);
+        assert_eq!(
+            clamp(10.0, 10.0),
+            Extent {
+                width: 120.0,
+                height: 36.0
+            }
+        );
+        assert_eq!(
+            clamp(200.2, 40.5),
+            Extent {
+                width: 201.0,
+                height: 41.0
+            }
+        );
+        assert_eq!(
+            clamp(900.0, 900.0),
+            Extent {
+                width: 480.0,
+                height: 360.0
+            }
+        );
+
+        // limits shrunk below their minimum keep to the maximum
+        let tiny = SizeLimits {
+            max_width: 0.0,
+            max_height: 10.0,
+            ..limits
+        };
+        assert_eq!(
+            Extent::default().clamp(&tiny),
+            Extent {
+                width: 0.0,
+                height: 10.0
+            }
+        );
+    }
+
+    #[test]
+    fn scales_extents_to_physical_sizes() {
+        let extent = Extent {
+            width: 201.0,
+            height: 41.0,
+        };
+        assert_eq!(extent.to_physical(1.0), PhysicalSize::new(201, 41));
+        assert_eq!(extent.to_physical(1.5), PhysicalSize::new(302, 62));
+        assert_eq!(extent.to_physical(2.0), PhysicalSize::new(402, 82));
+    }
+
+    #[test]
+    fn rounds_physical_sizes_to_the_nearest_pixel() {
+        let extent = Extent {
+            width: 120.2,
+            height: 36.4,
+        };
+        assert_eq!(extent.to_physical(1.0), PhysicalSize::new(120, 36));
+        assert_eq!(extent.to_physical(1.5), PhysicalSize::new(180, 55));
+        assert_eq!(extent.to_physical(2.0), PhysicalSize::new(240, 73));
+    }
+
+    #[test]
+    fn shrinks_limits_and_grows_extents_on_every_side() {
+        let limits = SizeLimits::default().shrink(10.0);
+        assert_eq!((limits.min_width, limits.min_height), (100.0, 16.0));
+        assert_eq!((limits.max_width, limits.max_height), (460.0, 340.0));
+        let extent = Extent {
+            width: 100.0,
+            height: 16.0,
+        };
+        assert_eq!(
+            extent.grow(10.0),
+            Extent {
+                width: 120.0,
+                height: 36.0,
+            }
+        );
+    }
+}
@@ -0,0 +1,368 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+/// Source of the current time, so animations can be driven deterministically.
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// How long the overlay takes to fade and how long it stays up, in
+/// milliseconds.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default)]
+pub struct Timing {
+    pub fade_in_ms: u64,
+    pub fade_out_ms: u64,
+    pub idle_timeout_ms: u64,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self {
+            fade_in_ms: 120,
+            fade_out_ms: 200,
+            idle_timeout_ms: 4000,
+        }
+    }
+}
+
+impl Timing {
+    fn fade_in(&self) -> Duration {
+        Duration::from_millis(self.fade_in_ms)
+    }
+
+    fn fade_out(&self) -> Duration {
+        Duration::from_millis(self.fade_out_ms)
+    }
+
+    fn idle_timeout(&self) -> Duration {
+        Duration::from_millis(self.idle_timeout_ms)
+    }
+}
+
+/// Fades start from the opacity the previous phase left off at, so reversing
+/// one halfway doesn't jump.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    Hidden,
+    FadingIn { start: Instant, from: f32 },
+    Shown { since: Instant },
+    FadingOut { start: Instant, from: f32 },
+}
+
+/// Whether the overlay is up, and how opaque it is.
+pub struct Lifecycle<C = SystemClock> {
+    clock: C,
+    timing: Timing,
+    phase: Phase,
+    hovered: bool,
+    pinned: bool,
+}
+
+impl<C: Clock> Lifecycle<C> {
+    pub fn new(clock: C, timing: Timing) -> Self {
+        Self {
+            clock,
+            timing,
+            phase: Phase::Hidden,
+            hovered: false,
+            pinned: false,
+        }
+    }
+
+    /// Keeps the overlay up until it's dismissed.
+    pub fn pin(&mut self) {
+        self.pinned = true;
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.pinned
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.phase != Phase::Hidden
+    }
+
+    pub fn is_animating(&self) -> bool {
+        matches!(self.phase, Phase::FadingIn { .. } | Phase::FadingOut { .. })
+    }
+
+    /// Shows the overlay, or restarts the idle timeout if it's up already.
+    pub fn show(&mut self) {
+        let now = self.clock.now();
+        self.phase = match self.phase {
+            Phase::FadingIn { .. } => return,
+            Phase::Shown { .. } => Phase::Shown { since: now },
+            Phase::Hidden | Phase::FadingOut { .. } => Phase::FadingIn {
+                start: now,
+                from: self.opacity_at(now),
+            },
+        };
+    }
+
+    /// Hides the overlay unless the pointer is over it or it's pinned.
+    pub fn hide(&mut self) {
+        if !self.hovered && !self.pinned {
+            self.dismiss();
+        }
+    }
+
+    /// Hides the overlay, even if the pointer is over it.
+    pub fn dismiss(&mut self) {
+        let now = self.clock.now();
+        if let Phase::FadingIn { .. } | Phase::Shown { .. } = self.phase {
+            self.phase = Phase::FadingOut {
+                start: now,
+                from: self.opacity_at(now),
+            };
+        }
+    }
+
+    /// Keeps the overlay up while hovered, bringing it back if it's fading
+    /// out, and restarts the idle timeout once the pointer leaves.
+    pub fn set_hovered(&mut self, hovered: bool) {
+        if self.hovered == hovered {
+            return;
+        }
+        self.hovered = hovered;
+
+        match self.phase {
+            Phase::FadingOut { .. } if hovered => self.show(),
+            Phase::Shown { .. } if !hovered => {
+                self.phase = Phase::Shown {
+                    since: self.clock.now(),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Moves on to whatever phase the current time falls into.
+    pub fn update(&mut self) {
+        let now = self.clock.now();
+        while let Some(end) = self.phase_end() {
+            if end > now {
+                break;
+            }
+            self.phase = match self.phase {
+                Phase::FadingIn { .. } => Phase::Shown { since: end },
+                Phase::Shown { .. } => Phase::FadingOut {
+                    start: end,
+                    from: 1.0,
+                },
+                Phase::FadingOut { .. } | Phase::Hidden => Phase::Hidden,
+            };
+        }
+    }
+
+    /// When [`update`](Self::update) next needs to run, short of animating.
+    pub fn deadline(&self) -> Option<Instant> {
+        match self.phase {
+            Phase::Shown { .. } => self.phase_end(),
+            _ => None,
+        }
+    }
+
+    pub fn opacity(&self) -> f32 {
+        self.opacity_at(self.clock.now())
+    }
+
+    fn opacity_at(&self, now: Instant) -> f32 {
+        let progress = |start: Instant, duration: Duration| {
+            if duration.is_zero() {
+                1.0
+            } else {
+                now.saturating_duration_since(start).as_secs_f32() / duration.as_secs_f32()
+            }
+        };
+        match self.phase {
+            Phase::Hidden => 0.0,
+            Phase::FadingIn { start, from } => {
+                (from + progress(start, self.timing.fade_in())).min(1.0)
+            }
+            Phase::Shown { .. } => 1.0,
+            Phase::FadingOut { start, from } => {
+                (from - progress(start, self.timing.fade_out())).max(0.0)
+            }
+        }
+    }
+
+    /// When the current phase is over, if it ends by itself.
+    fn phase_end(&self) -> Option<Instant> {
+        match self.phase {
+            Phase::Hidden => None,
+            Phase::FadingIn { start, from } => {
+                Some(start + self.timing.fade_in().mul_f32(1.0 - from))
+            }
+            Phase::Shown { .. } if self.hovered || self.pinned => None,
+            Phase::Shown { since } => Some(since + self.timing.idle_timeout()),
+            Phase::FadingOut { start, from } => Some(start + self.timing.fade_out().mul_f32(from)),
+        }
+    }
+}
+
+/// A clock that only moves when told to, shared by its clones.
+#[cfg(test)]
+#[derive(Clone)]
+pub struct ManualClock(std::rc::Rc<std::cell::Cell<Instant>>);
+
+#[cfg(test)]
+impl ManualClock {
+    pub fn new() -> Self {
+        Self(std::rc::Rc::new(std::cell::Cell::new(Instant::now())))
+    }
+
+    pub fn advance(&self, ms: u64) {
+        self.0.set(self.0.get() + Duration::from_millis(ms));
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.0.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lifecycle() -> (Lifecycle<ManualClock>, ManualClock) {
+        let clock = ManualClock::new();
+        (Lifecycle::new(clock.clone(), Timing::default()), clock)
+    }
+
+    /// Advances the clock by `ms` and updates the lifecycle.
+    fn tick(lifecycle: &mut Lifecycle<ManualClock>, clock: &ManualClock, ms: u64) {
+        clock.advance(ms);
+        lifecycle.update();
+    }
+
+    #[test]
+    fn fades_in_stays_up_and_fades_out() {
+        let (mut lifecycle, clock) = lifecycle();
+        assert_eq!(lifecycle.phase(), Phase::Hidden);
+        assert_eq!(lifecycle.opacity(), 0.0);
+
+        lifecycle.show();
+        assert!(matches!(lifecycle.phase(), Phase::FadingIn { .. }));
+        tick(&mut lifecycle, &clock, 60);
+        assert_eq!(lifecycle.opacity(), 0.5);
+
+        tick(&mut lifecycle, &clock, 60);
+        assert!(matches!(lifecycle.phase(), Phase::Shown { .. }));
+        assert_eq!(lifecycle.opacity(), 1.0);
+
+        lifecycle.hide();
+        assert!(matches!(lifecycle.phase(), Phase::FadingOut { .. }));
+        tick(&mut lifecycle, &clock, 100);
+        assert_eq!(lifecycle.opacity(), 0.5);
+
+        tick(&mut lifecycle, &clock, 100);
+        assert_eq!(lifecycle.phase(), Phase::Hidden);
+        assert!(!lifecycle.is_visible());
+    }
+
+    #[test]
+    fn fades_out_after_the_idle_timeout() {
+        let (mut lifecycle, clock) = lifecycle();
+        lifecycle.show();
+        tick(&mut lifecycle, &clock, 120);
+        let deadline = lifecycle.deadline().unwrap();
+        assert_eq!(deadline, clock.now() + Duration::from_millis(4000));
+
+        tick(&mut lifecycle, &clock, 3999);
+        assert!(matches!(lifecycle.phase(), Phase::Shown { .. }));
+        tick(&mut lifecycle, &clock, 1);
+        assert!(matches!(lifecycle.phase(), Phase::FadingOut { .. }));
+        tick(&mut lifecycle, &clock, 200);
+        assert_eq!(lifecycle.phase(), Phase::Hidden);
+    }
+
+    #[test]
+    fn restarts_the_idle_timeout_when_shown_again() {
+        let (mut lifecycle, clock) = lifecycle();
+        lifecycle.show();
+        tick(&mut lifecycle, &clock, 120 + 3000);
+        lifecycle.show();
+        tick(&mut lifecycle, &clock, 3000);
+        assert!(matches!(lifecycle.phase(), Phase::Shown { .. }));
+    }
+
+    #[test]
+    fn catches_up_on_every_phase_it_missed() {
+        let (mut lifecycle, clock) = lifecycle();
+        lifecycle.show();
+        tick(&mut lifecycle, &clock, 10_000);
+        assert_eq!(lifecycle.phase(), Phase::Hidden);
+    }
+
+    #[test]
+    fn stays_up_while_hovered() {
+        let (mut lifecycle, clock) = lifecycle();
+        lifecycle.show();
+        tick(&mut lifecycle, &clock, 120);
+        lifecycle.set_hovered(true);
+        assert_eq!(lifecycle.deadline(), None);
+
+        lifecycle.hide();
+        tick(&mut lifecycle, &clock, 10_000);
+        assert!(matches!(lifecycle.phase(), Phase::Shown { .. }));
+
+        // the idle timeout starts over once the pointer leaves
+        lifecycle.set_hovered(false);
+        tick(&mut lifecycle, &clock, 3999);
+        assert!(matches!(lifecycle.phase(), Phase::Shown { .. }));
+        tick(&mut lifecycle, &clock, 1);
+        assert!(matches!(lifecycle.phase(), Phase::FadingOut { .. }));
+    }
+
+    #[test]
+    fn comes_back_when_hovered_while_fading_out() {
+        let (mut lifecycle, clock) = lifecycle();
+        lifecycle.show();
+        tick(&mut lifecycle, &clock, 120);
+        lifecycle.hide();
+        tick(&mut lifecycle, &clock, 50);
+        assert_eq!(lifecycle.opacity(), 0.75);
+
+        // fading back in starts where fading out left off
+        lifecycle.set_hovered(true);
+        assert!(matches!(
+            lifecycle.phase(),
+            Phase::FadingIn { from: 0.75, .. }
+        ));
+        tick(&mut lifecycle, &clock, 30);
+        assert!(matches!(lifecycle.phase(), Phase::Shown { .. }));
+    }
+
+    #[test]
+    fn dismisses_even_when_hovered_or_pinned() {
+        let (mut lifecycle, clock) = lifecycle();
+        lifecycle.show();
+        lifecycle.pin();
+        lifecycle.set_hovered(true);
+        tick(&mut lifecycle, &clock, 120);
+
+        lifecycle.hide();
+        assert!(matches!(lifecycle.phase(), Phase::Shown { .. }));
+        lifecycle.dismiss();
+        tick(&mut lifecycle, &clock, 200);
+        assert_eq!(lifecycle.phase(), Phase::Hidden);
+    }
+}
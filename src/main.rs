@@ -1,15 +1,117 @@
-#![feature(macro_metavar_expr_concat)]
+#![cfg_attr(target_os = "macos", feature(macro_metavar_expr_concat))]
 
-use application::App;
+use anyhow::Context;
+use card::Card;
+use config::Config;
+use fonts::Fonts;
+use history::{History, Query};
+use overlay::Overlay;
+use theme::Appearance;
 
+// the overlay only runs on macOS; elsewhere what it uses builds for the
+// tools and the tests
+#[cfg(target_os = "macos")]
 pub mod accessibility;
+#[cfg(target_os = "macos")]
 mod application;
+mod capture;
+mod card;
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod clipboard;
+mod config;
+#[cfg(target_os = "macos")]
 mod controller;
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
 mod event;
-
-#[cfg(not(target_os = "macos"))]
-compile_error!("crate only supported on macOS");
+mod fonts;
+mod highlight;
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod history;
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod interaction;
+mod layout;
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod lifecycle;
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod normalization;
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod overlay;
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod pins;
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod provider;
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod redaction;
+mod render;
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod rules;
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod sink;
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod stabilizer;
+mod theme;
 
 fn main() -> anyhow::Result<()> {
-    App::default().run()
+    let config = Config::load()?;
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let [flag, path, text] = &args[..]
+        && flag == "--snapshot"
+    {
+        let font_system = Fonts::new(config.fonts.clone()).font_system();
+        let mut overlay = Overlay::new(font_system, config.size, config.theme(Appearance::Light));
+        return overlay.snapshot(Card::text(text), 2.0)?.write_png(path);
+    }
+    if let [flag, text] = &args[..]
+        && flag == "--history"
+    {
+        return print_history(text);
+    }
+    if let [flag] = &args[..]
+        && flag == "--outbox"
+    {
+        return print_outbox(&config);
+    }
+
+    #[cfg(target_os = "macos")]
+    return application::App::new(config).run();
+    #[cfg(not(target_os = "macos"))]
+    anyhow::bail!("the overlay is only supported on macOS");
+}
+
+/// Prints the latest captures matching `text`, one per line.
+fn print_history(text: &str) -> anyhow::Result<()> {
+    let path = History::path().context("no data directory")?;
+    let query = Query {
+        text: Some(text.to_owned()),
+        limit: 20,
+        ..Query::default()
+    };
+    for entry in History::open(&path)?.query(&query)? {
+        let capture = entry.capture;
+        let app = capture.app_name.or(capture.bundle_id).unwrap_or_default();
+        let line = capture.text.lines().next().unwrap_or_default();
+        println!("{}\t{app}\t{line}", entry.id);
+    }
+    Ok(())
+}
+
+/// Prints how far behind each sink is, one per line.
+fn print_outbox(config: &Config) -> anyhow::Result<()> {
+    for sink in &config.sinks {
+        let status = sink.outbox(config.outbox)?.status()?;
+        let lag = status
+            .oldest
+            .and_then(|oldest| oldest.elapsed().ok())
+            .unwrap_or_default();
+        println!(
+            "{}\t{} pending ({} bytes)\t{}s behind\t{} dead letters",
+            sink.name(),
+            status.pending,
+            status.bytes,
+            lag.as_secs(),
+            status.dead_letters,
+        );
+    }
+    Ok(())
 }
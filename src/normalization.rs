@@ -0,0 +1,230 @@
+use serde::Deserialize;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::capture::Capture;
+
+/// Characters that take up no space and are dropped, the soft hyphen
+/// included. The zero-width joiner and non-joiner are kept, since emoji
+/// sequences and scripts like Persian and Devanagari depend on them.
+const ZERO_WIDTH: [char; 4] = ['\u{200B}', '\u{2060}', '\u{FEFF}', '\u{00AD}'];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Form {
+    Nfc,
+    /// Also replaces compatibility characters like ligatures, full-width
+    /// letters and non-breaking spaces with their plain equivalents.
+    Nfkc,
+}
+
+/// How selected text is cleaned up, mostly of what copying from PDFs and web
+/// pages leaves in it.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default)]
+pub struct Normalization {
+    pub form: Option<Form>,
+    pub strip_zero_width: bool,
+    /// Joins words split across lines with a hyphen.
+    pub dehyphenate: bool,
+    /// Joins lines within paragraphs, which are kept apart by blank lines.
+    pub join_lines: bool,
+    /// Replaces curly quotes with straight ones and dashes with hyphens.
+    pub straighten_quotes: bool,
+    /// Replaces runs of spaces and tabs with a single space, and trims lines.
+    pub collapse_whitespace: bool,
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Self {
+            form: Some(Form::Nfc),
+            strip_zero_width: true,
+            dehyphenate: false,
+            join_lines: false,
+            straighten_quotes: false,
+            collapse_whitespace: false,
+        }
+    }
+}
+
+impl Normalization {
+    /// Normalizes the text of `capture`, keeping what it was as `original` if
+    /// that changed it.
+    pub fn apply(&self, capture: &mut Capture) {
+        let text = self.normalize(&capture.text);
+        if text != capture.text {
+            capture.original = Some(std::mem::replace(&mut capture.text, text));
+        }
+    }
+
+    pub fn normalize(&self, text: &str) -> String {
+        let mut text = text.to_owned();
+        if self.strip_zero_width {
+            text.retain(|c| !ZERO_WIDTH.contains(&c));
+        }
+        text = match self.form {
+            Some(Form::Nfc) => text.nfc().collect(),
+            Some(Form::Nfkc) => text.nfkc().collect(),
+            None => text,
+        };
+        if self.dehyphenate {
+            text = dehyphenate(&text);
+        }
+        if self.join_lines {
+            text = join_lines(&text);
+        }
+        if self.straighten_quotes {
+            text = straighten_quotes(&text);
+        }
+        if self.collapse_whitespace {
+            text = collapse_whitespace(&text);
+        }
+        text
+    }
+}
+
+/// Removes hyphens at the end of lines that split a word, like `exam-` with
+/// `ple` on the next line. Hyphens before a line that doesn't go on in
+/// lowercase are kept, but a compound split at its own hyphen, like `well-`
+/// with `known`, can't be told apart and loses it.
+fn dehyphenate(text: &str) -> String {
+    let mut lines: Vec<String> = text.split('\n').map(str::to_owned).collect();
+    let mut index = 0;
+    while index + 1 < lines.len() {
+        let trimmed = lines[index].trim_end();
+        let start = trimmed
+            .strip_suffix(['-', '\u{2010}'])
+            .filter(|start| start.chars().next_back().is_some_and(char::is_alphabetic));
+        let next = lines[index + 1].trim_start();
+        if let Some(start) = start
+            && next.chars().next().is_some_and(char::is_lowercase)
+        {
+            // the rest of the word moves up, and the line it leaves can
+            // still end in a split word of its own
+            let (rest, after) = next.split_at(next.find(char::is_whitespace).unwrap_or(next.len()));
+            let (joined, after) = (format!("{start}{rest}"), after.trim_start().to_owned());
+            lines[index] = joined;
+            if after.is_empty() {
+                lines.remove(index + 1);
+                continue;
+            }
+            lines[index + 1] = after;
+        }
+        index += 1;
+    }
+    lines.join("\n")
+}
+
+/// Replaces single line breaks with spaces, keeping blank lines between
+/// paragraphs as one.
+fn join_lines(text: &str) -> String {
+    text.split('\n')
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .split(|line| line.is_empty())
+        .filter(|paragraph| !paragraph.is_empty())
+        .map(|paragraph| paragraph.join(" "))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn straighten_quotes(text: &str) -> String {
+    let mut straight = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' | '\u{2032}' => straight.push('\''),
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' | '\u{2033}' => straight.push('"'),
+            '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' | '\u{2212}' => straight.push('-'),
+            '\u{2014}' | '\u{2015}' => straight.push_str("--"),
+            c => straight.push(c),
+        }
+    }
+    straight
+}
+
+/// Replaces runs of whitespace within lines, non-breaking spaces included,
+/// with a single space, and trims lines and the text as a whole.
+fn collapse_whitespace(text: &str) -> String {
+    text.split('\n')
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_zero_width_characters_but_joiners() {
+        let normalization = Normalization::default();
+        assert_eq!(
+            normalization.normalize("zero\u{200B}width\u{FEFF} soft\u{00AD}hyphen"),
+            "zerowidth softhyphen"
+        );
+        // a family emoji, and a Persian word with a non-joiner
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        assert_eq!(normalization.normalize(family), family);
+        let persian = "\u{0645}\u{06CC}\u{200C}\u{062E}\u{0648}\u{0627}\u{0647}\u{0645}";
+        assert_eq!(normalization.normalize(persian), persian);
+    }
+
+    #[test]
+    fn dehyphenates_words_split_across_lines() {
+        assert_eq!(dehyphenate("an exam-\nple of it"), "an example\nof it");
+        assert_eq!(dehyphenate("an exam-\nple"), "an example");
+        // the line the rest of a word leaves can end in a split word too
+        assert_eq!(
+            dehyphenate("one hy-\nphen-\nated word"),
+            "one hyphenated\nword"
+        );
+        assert_eq!(dehyphenate("an exam\u{2010}\n  ple"), "an example");
+    }
+
+    #[test]
+    fn keeps_hyphens_before_anything_but_lowercase() {
+        assert_eq!(dehyphenate("COVID-\n19"), "COVID-\n19");
+        assert_eq!(dehyphenate("Jean-\nPaul"), "Jean-\nPaul");
+        assert_eq!(dehyphenate("a list:\n-\nitem"), "a list:\n-\nitem");
+        assert_eq!(dehyphenate("2-\nway"), "2-\nway");
+        // compounds can't be told apart from split words
+        assert_eq!(dehyphenate("well-\nknown"), "wellknown");
+    }
+
+    #[test]
+    fn joins_lines_within_paragraphs() {
+        assert_eq!(
+            join_lines("first line\n  second line\n\n\n\nnext paragraph\n"),
+            "first line second line\n\nnext paragraph"
+        );
+        assert_eq!(join_lines("\n\none\n"), "one");
+        assert_eq!(join_lines(""), "");
+    }
+
+    #[test]
+    fn collapses_whitespace_within_lines() {
+        assert_eq!(
+            collapse_whitespace("  a \t b\u{00A0}\u{00A0}c  \n\n d  e \n"),
+            "a b c\n\nd e"
+        );
+        assert_eq!(collapse_whitespace(" \t "), "");
+    }
+
+    #[test]
+    fn keeps_the_original_if_changed() {
+        let normalization = Normalization {
+            collapse_whitespace: true,
+            ..Normalization::default()
+        };
+        let mut capture = Capture::new("a  b".to_owned(), crate::capture::Trigger::Poll);
+        normalization.apply(&mut capture);
+        assert_eq!(capture.text, "a b");
+        assert_eq!(capture.original.as_deref(), Some("a  b"));
+
+        let mut capture = Capture::new("a b".to_owned(), crate::capture::Trigger::Poll);
+        normalization.apply(&mut capture);
+        assert_eq!(capture.original, None);
+    }
+}
@@ -0,0 +1,674 @@
+use winit::dpi::PhysicalSize;
+
+use crate::card::{self, Card, CardLayout, Command, Measured, Section, Spacing};
+use crate::highlight::{self, Language};
+use crate::interaction::{ButtonState, Interaction};
+use crate::layout::{self, Extent, Overflow, Rect, RichText, SizeLimits};
+use crate::render::{ImageQuad, Panel, Pixmap, Renderer, Scene, SoftwareRenderer};
+use crate::theme::Theme;
+
+/// The content shown in the overlay, independent of the window and renderer
+/// it ends up on.
+pub struct Overlay {
+    font_system: glyphon::FontSystem,
+    header_buffer: glyphon::Buffer,
+    body_buffer: glyphon::Buffer,
+    footer_buffer: glyphon::Buffer,
+    action_buffers: Vec<glyphon::Buffer>,
+    card: Card,
+    card_layout: CardLayout,
+    interaction: Interaction,
+    /// How far the body is scrolled down, and how far it can be.
+    scroll: f32,
+    max_scroll: f32,
+    size_limits: SizeLimits,
+    theme: Theme,
+    /// Bumped whenever what's drawn changes, so unchanged frames can be
+    /// skipped.
+    revision: u64,
+}
+
+impl Overlay {
+    pub fn new(
+        mut font_system: glyphon::FontSystem,
+        size_limits: SizeLimits,
+        theme: Theme,
+    ) -> Self {
+        let mut buffer = |section: Section| {
+            glyphon::Buffer::new(&mut font_system, section.style(&theme).metrics)
+        };
+
+        Self {
+            header_buffer: buffer(Section::Header),
+            body_buffer: buffer(Section::Body),
+            footer_buffer: buffer(Section::Footer),
+            action_buffers: Vec::new(),
+            font_system,
+            card: Card::default(),
+            card_layout: CardLayout::default(),
+            interaction: Interaction::default(),
+            scroll: 0.0,
+            max_scroll: 0.0,
+            size_limits,
+            theme,
+            revision: 0,
+        }
+    }
+
+    /// Lays out `card` and returns the logical extent the overlay window
+    /// needs, including the panel chrome.
+    pub fn set_card(&mut self, card: Card) -> Extent {
+        let limits = self.size_limits.shrink(self.theme.inset());
+        let spacing = self.spacing();
+        let gap = spacing.gap;
+        let icon_size = card
+            .icon
+            .map(|_| Section::Header.style(&self.theme).metrics.line_height);
+
+        let header_limits = SizeLimits {
+            max_width: (limits.max_width - icon_size.map_or(0.0, |size| size + gap)).max(0.0),
+            ..limits
+        };
+        let header = self.shape(Section::Header, &card.title, None, &header_limits);
+        let footer = self.shape(Section::Footer, &card.footer, None, &limits);
+        let actions = self.shape_actions(&card, &limits);
+
+        let header_row = (header.is_some() || icon_size.is_some()).then(|| {
+            let header_height = header.map_or(0.0, |extent| extent.height);
+            header_height.max(icon_size.unwrap_or(0.0))
+        });
+        let actions_row = actions
+            .iter()
+            .map(|label| label.grow(spacing.button_padding).height)
+            .reduce(f32::max);
+        let taken: f32 = [header_row, actions_row, footer.map(|extent| extent.height)]
+            .into_iter()
+            .flatten()
+            .map(|height| height + gap)
+            .sum();
+        let image = card.image.as_ref().map(|image| {
+            // fit into a square two body lines high, keeping the aspect ratio
+            let size = Section::Body.style(&self.theme).metrics.line_height * 2.0;
+            let scale = size / image.width().max(image.height()) as f32;
+            Extent {
+                width: (image.width() as f32 * scale).round(),
+                height: (image.height() as f32 * scale).round(),
+            }
+        });
+        let image_width = image.map_or(0.0, |image| image.width + gap);
+        let body_limits = SizeLimits {
+            min_width: (limits.min_width - image_width).max(0.0),
+            max_width: (limits.max_width - image_width).max(0.0),
+            max_height: (limits.max_height - taken).max(0.0),
+            ..limits
+        };
+        let body = self.shape(Section::Body, &card.body, card.language, &body_limits);
+
+        let measured = Measured {
+            icon_size,
+            header,
+            image,
+            body,
+            actions,
+            footer,
+        };
+        self.card_layout = card::layout_card(&measured, &spacing);
+        self.interaction.reset(card.actions.len());
+
+        // new content starts at the top, re-laying out the same card keeps
+        // its scroll position
+        let content_height = layout::measure(&self.body_buffer).height;
+        let viewport_height = self.card_layout.body.map_or(0.0, |rect| rect.height);
+        self.max_scroll = (content_height - viewport_height).max(0.0);
+        if card != self.card {
+            self.scroll = 0.0;
+        }
+        self.scroll = self.scroll.min(self.max_scroll);
+
+        self.card = card;
+        self.revision += 1;
+        self.card_layout
+            .extent
+            .clamp(&limits)
+            .grow(self.theme.inset())
+    }
+
+    pub fn card(&self) -> &Card {
+        &self.card
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Switches to `theme`, re-shaping the current card with its typography.
+    pub fn set_theme(&mut self, theme: Theme) -> Extent {
+        self.theme = theme;
+        let card = self.card.clone();
+        self.set_card(card)
+    }
+
+    /// Moves the pointer to (`x`, `y`) in logical window coordinates. Returns
+    /// whether the overlay needs to be redrawn.
+    pub fn pointer_moved(&mut self, x: f32, y: f32) -> bool {
+        let inset = self.theme.inset();
+        let hit = self.card_layout.action_at(x - inset, y - inset);
+        let changed = self.interaction.pointer_moved(hit);
+        self.changed(changed)
+    }
+
+    pub fn pointer_left(&mut self) -> bool {
+        let changed = self.interaction.pointer_moved(None);
+        self.changed(changed)
+    }
+
+    pub fn pointer_pressed(&mut self) -> bool {
+        let changed = self.interaction.pointer_pressed();
+        self.changed(changed)
+    }
+
+    /// Releases the pointer, returning the command of the button it clicked.
+    pub fn pointer_released(&mut self) -> Option<Command> {
+        self.revision += 1;
+        let index = self.interaction.pointer_released()?;
+        Some(self.card.actions[index].command.clone())
+    }
+
+    /// Scrolls the body down by `delta` logical pixels, or up if negative.
+    /// Returns whether it moved.
+    pub fn scroll_by(&mut self, delta: f32) -> bool {
+        let scroll = (self.scroll + delta).clamp(0.0, self.max_scroll);
+        let moved = scroll != self.scroll;
+        self.scroll = scroll;
+        self.changed(moved)
+    }
+
+    /// Height of a line of the body, which is what a wheel notch scrolls by.
+    pub fn line_height(&self) -> f32 {
+        self.body_buffer.metrics().line_height
+    }
+
+    pub fn focus_next(&mut self, backwards: bool) {
+        self.interaction.focus_next(backwards);
+        self.revision += 1;
+    }
+
+    /// Returns the command of the focused button, or of the first one.
+    pub fn activate(&self) -> Option<Command> {
+        let index = self.interaction.activated()?;
+        Some(self.card.actions[index].command.clone())
+    }
+
+    pub fn render(
+        &mut self,
+        renderer: &mut dyn Renderer,
+        size: PhysicalSize<u32>,
+        scale: f32,
+        opacity: f32,
+    ) -> anyhow::Result<()> {
+        let (width, height) = (size.width as f32, size.height as f32);
+        let margin = self.theme.margin() * scale;
+        let inset = self.theme.inset() * scale;
+        let bounds = glyphon::TextBounds {
+            left: inset as i32,
+            top: inset as i32,
+            right: (width - inset) as i32,
+            bottom: (height - inset) as i32,
+        };
+
+        // the icon is drawn as part of the header, to the left of its text
+        let icons: Vec<glyphon::CustomGlyph> = self
+            .card
+            .icon
+            .zip(self.card_layout.icon)
+            .zip(self.card_layout.header)
+            .map(|((icon, rect), header)| glyphon::CustomGlyph {
+                id: icon.id(),
+                left: rect.x - header.x,
+                top: rect.y - header.y,
+                width: rect.width,
+                height: rect.height,
+                color: Some(fade(self.theme.accent, opacity)),
+                snap_to_physical_pixel: true,
+                metadata: 0,
+            })
+            .into_iter()
+            .collect();
+
+        let buffers = [&self.header_buffer, &self.body_buffer, &self.footer_buffer];
+        let mut text_areas: Vec<_> = Section::ALL
+            .into_iter()
+            .zip(buffers)
+            .filter_map(|(section, buffer)| {
+                let rect = self.card_layout.section(section)?;
+                let (scroll, bounds) = match section.overflow() {
+                    Overflow::Ellipsize => (0.0, bounds),
+                    Overflow::Scroll => (
+                        self.scroll,
+                        glyphon::TextBounds {
+                            top: (inset + rect.y * scale) as i32,
+                            bottom: (inset + (rect.y + rect.height) * scale) as i32,
+                            ..bounds
+                        },
+                    ),
+                };
+                Some(glyphon::TextArea {
+                    buffer,
+                    left: inset + rect.x * scale,
+                    top: inset + (rect.y - scroll) * scale,
+                    scale,
+                    bounds,
+                    default_color: fade(section.style(&self.theme).color, opacity),
+                    custom_glyphs: if section == Section::Header {
+                        &icons
+                    } else {
+                        &[]
+                    },
+                })
+            })
+            .collect();
+
+        let mut panels = vec![Panel {
+            rect: [margin, margin, width - 2.0 * margin, height - 2.0 * margin],
+            fill: fade(self.theme.background, opacity),
+            border: fade(self.theme.border, opacity),
+            shadow: fade(self.theme.shadow, opacity),
+            corner_radius: self.theme.corner_radius * scale,
+            border_width: self.theme.border_width * scale,
+            shadow_radius: self.theme.shadow_radius * scale,
+        }];
+
+        if let Some(body) = self.card_layout.body
+            && self.max_scroll > 0.0
+        {
+            panels.push(self.scroll_indicator(body, width - inset, scale, opacity));
+        }
+
+        let images = self
+            .card
+            .image
+            .clone()
+            .zip(self.card_layout.image)
+            .map(|(image, rect)| ImageQuad {
+                image,
+                rect: [
+                    inset + rect.x * scale,
+                    inset + rect.y * scale,
+                    rect.width * scale,
+                    rect.height * scale,
+                ],
+                corner_radius: self.theme.corner_radius / 2.0 * scale,
+                opacity,
+            })
+            .into_iter()
+            .collect();
+
+        let button_style = card::button_style(&self.theme);
+        let button_padding = self.spacing().button_padding;
+        let accent = self.theme.accent;
+        for (index, (rect, buffer)) in self
+            .card_layout
+            .actions
+            .iter()
+            .zip(&self.action_buffers)
+            .enumerate()
+        {
+            let alpha = match self.interaction.state(index) {
+                ButtonState::Normal => 0x1f,
+                ButtonState::Hovered => 0x33,
+                ButtonState::Pressed => 0x55,
+            };
+            let focused = self.interaction.is_focused(index);
+            panels.push(Panel {
+                rect: [
+                    inset + rect.x * scale,
+                    inset + rect.y * scale,
+                    rect.width * scale,
+                    rect.height * scale,
+                ],
+                fill: fade(
+                    glyphon::Color::rgba(accent.r(), accent.g(), accent.b(), alpha),
+                    opacity,
+                ),
+                border: fade(accent, opacity),
+                shadow: glyphon::Color::rgba(0, 0, 0, 0),
+                corner_radius: self.theme.corner_radius / 2.0 * scale,
+                border_width: if focused {
+                    self.theme.border_width.max(1.0) * scale
+                } else {
+                    0.0
+                },
+                shadow_radius: 0.0,
+            });
+            text_areas.push(glyphon::TextArea {
+                buffer,
+                left: inset + (rect.x + button_padding) * scale,
+                top: inset + (rect.y + button_padding) * scale,
+                scale,
+                bounds,
+                default_color: fade(button_style.color, opacity),
+                custom_glyphs: &[],
+            });
+        }
+
+        let scene = Scene {
+            background: glyphon::Color::rgba(0, 0, 0, 0),
+            panels,
+            images,
+            text_areas,
+        };
+        renderer.render(&mut self.font_system, scene)
+    }
+
+    /// Renders `card` on the CPU at `scale`, as it would appear on screen.
+    pub fn snapshot(&mut self, card: Card, scale: f64) -> anyhow::Result<Pixmap> {
+        let extent = self.set_card(card);
+        let size = extent.to_physical(scale);
+
+        let mut renderer = SoftwareRenderer::headless(size);
+        self.render(&mut renderer, size, scale as f32, 1.0)?;
+        Ok(renderer.into_pixmap())
+    }
+
+    /// Passes `changed` through, bumping the revision if it's set.
+    fn changed(&mut self, changed: bool) -> bool {
+        if changed {
+            self.revision += 1;
+        }
+        changed
+    }
+
+    /// A thin bar next to the body, as tall relative to it as the visible part
+    /// is to all of the body, with its right edge at `right`.
+    fn scroll_indicator(&self, body: Rect, right: f32, scale: f32, opacity: f32) -> Panel {
+        const WIDTH: f32 = 3.0;
+
+        let content_height = body.height + self.max_scroll;
+        let height = body.height * body.height / content_height;
+        let top = body.y + self.scroll / self.max_scroll * (body.height - height);
+        let muted = self.theme.muted;
+        Panel {
+            rect: [
+                right + (self.theme.padding / 2.0 - WIDTH / 2.0) * scale,
+                self.theme.inset() * scale + top * scale,
+                WIDTH * scale,
+                height * scale,
+            ],
+            fill: fade(
+                glyphon::Color::rgba(muted.r(), muted.g(), muted.b(), 0x80),
+                opacity,
+            ),
+            border: glyphon::Color::rgba(0, 0, 0, 0),
+            shadow: glyphon::Color::rgba(0, 0, 0, 0),
+            corner_radius: WIDTH / 2.0 * scale,
+            border_width: 0.0,
+            shadow_radius: 0.0,
+        }
+    }
+
+    fn spacing(&self) -> Spacing {
+        Spacing {
+            gap: self.theme.padding / 2.0,
+            button_padding: self.theme.padding / 2.0,
+        }
+    }
+
+    /// Shapes `text` into the buffer of `section`, returning `None` when there
+    /// is nothing to show. Code is set in the code family and highlighted.
+    fn shape(
+        &mut self,
+        section: Section,
+        text: &str,
+        language: Option<Language>,
+        limits: &SizeLimits,
+    ) -> Option<Extent> {
+        let style = match language {
+            Some(_) => card::code_style(&self.theme),
+            None => section.style(&self.theme),
+        };
+        let limits = SizeLimits {
+            max_lines: section.max_lines().unwrap_or(limits.max_lines),
+            ..*limits
+        };
+
+        let attrs = glyphon::Attrs::new()
+            .family(self.theme.family())
+            .weight(style.weight);
+        let rich_text = match language {
+            Some(language) => {
+                let attrs = attrs.family(self.theme.code_family());
+                RichText {
+                    text,
+                    spans: highlight::highlight(language, text)
+                        .into_iter()
+                        .map(|(range, token)| {
+                            (range, attrs.clone().color(self.theme.token_color(token)))
+                        })
+                        .collect(),
+                    attrs,
+                }
+            }
+            None => RichText::plain(text, attrs),
+        };
+
+        let buffer = match section {
+            Section::Header => &mut self.header_buffer,
+            Section::Body => &mut self.body_buffer,
+            Section::Footer => &mut self.footer_buffer,
+        };
+        let extent = shape_text(
+            &mut self.font_system,
+            buffer,
+            style.metrics,
+            &rich_text,
+            &limits,
+            section.overflow(),
+        );
+
+        (!text.is_empty()).then_some(extent)
+    }
+
+    /// Shapes the labels of the action buttons of `card`, one line each.
+    fn shape_actions(&mut self, card: &Card, limits: &SizeLimits) -> Vec<Extent> {
+        let style = card::button_style(&self.theme);
+        let limits = SizeLimits {
+            max_width: limits.max_width - 2.0 * self.spacing().button_padding,
+            max_lines: 1,
+            ..*limits
+        };
+
+        self.action_buffers.resize_with(card.actions.len(), || {
+            glyphon::Buffer::new(&mut self.font_system, style.metrics)
+        });
+        let attrs = glyphon::Attrs::new()
+            .family(self.theme.family())
+            .weight(style.weight);
+        card.actions
+            .iter()
+            .zip(&mut self.action_buffers)
+            .map(|(action, buffer)| {
+                shape_text(
+                    &mut self.font_system,
+                    buffer,
+                    style.metrics,
+                    &RichText::plain(&action.label, attrs.clone()),
+                    &limits,
+                    Overflow::Ellipsize,
+                )
+            })
+            .collect()
+    }
+}
+
+/// Scales the alpha of `color` by `opacity`, to fade the overlay in and out.
+fn fade(color: glyphon::Color, opacity: f32) -> glyphon::Color {
+    let alpha = (color.a() as f32 * opacity).round() as u8;
+    glyphon::Color::rgba(color.r(), color.g(), color.b(), alpha)
+}
+
+/// Shapes `text` into `buffer` at `metrics`, sizing the buffer to fit all of
+/// it, and returns the extent of the part that is visible at once.
+fn shape_text(
+    font_system: &mut glyphon::FontSystem,
+    buffer: &mut glyphon::Buffer,
+    metrics: glyphon::Metrics,
+    text: &RichText,
+    limits: &SizeLimits,
+    overflow: Overflow,
+) -> Extent {
+    let limits = SizeLimits {
+        min_width: 0.0,
+        min_height: 0.0,
+        ..*limits
+    };
+    buffer.set_metrics(font_system, metrics);
+    let extent = layout::layout_text(font_system, buffer, text, &limits, overflow);
+    let content_height = layout::measure(buffer).height.max(extent.height);
+    buffer.set_size(font_system, Some(limits.max_width), Some(content_height));
+    extent
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::fonts::bundled_font_system;
+
+    /// Channels of snapshots may differ by this much from the golden image,
+    /// for rounding that differs between platforms.
+    const TOLERANCE: u8 = 2;
+
+    /// Renders `card` and compares it with `tests/snapshots/<name>.png`.
+    /// Run with `UPDATE_SNAPSHOTS=1` to write the snapshot instead.
+    fn assert_snapshot(name: &str, card: Card, theme: Theme) {
+        let mut overlay = Overlay::new(bundled_font_system(), SizeLimits::default(), theme);
+        let pixmap = overlay.snapshot(card, 2.0).unwrap();
+
+        let golden: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "snapshots"]
+            .iter()
+            .collect::<PathBuf>()
+            .join(format!("{name}.png"));
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::create_dir_all(golden.parent().unwrap()).unwrap();
+            pixmap.write_png(&golden).unwrap();
+            return;
+        }
+
+        let expected = Pixmap::read_png(&golden).unwrap();
+        if !pixmap.matches(&expected, TOLERANCE) {
+            let actual = std::env::temp_dir().join(format!("{name}.actual.png"));
+            pixmap.write_png(&actual).unwrap();
+            panic!(
+                "snapshot {name} differs from {}, see {}",
+                golden.display(),
+                actual.display()
+            );
+        }
+    }
+
+    #[test]
+    fn snapshots_a_text_card() {
+        assert_snapshot(
+            "text",
+            Card::text("The quick brown fox jumps over the lazy dog."),
+            Theme::light(),
+        );
+    }
+
+    #[test]
+    fn snapshots_a_code_card() {
+        assert_snapshot(
+            "code",
+            Card::code("fn main() {\n    println!(\"hi\");\n}", Language::Rust),
+            Theme::dark(),
+        );
+    }
+
+    #[test]
+    fn snapshots_a_color_swatch() {
+        assert_snapshot("color", Card::text("#3366ff"), Theme::light());
+    }
+
+    #[test]
+    fn fits_cards_into_tiny_limits() {
+        let limits = SizeLimits {
+            max_width: 40.0,
+            max_height: 10.0,
+            ..SizeLimits::default()
+        };
+        let mut overlay = Overlay::new(bundled_font_system(), limits, Theme::light());
+        let extent = overlay.set_card(Card::text("#3366ff is a shade of blue"));
+        // nothing but the panel chrome is left
+        let inset = Theme::light().inset();
+        assert_eq!(extent, Extent::default().grow(inset));
+    }
+
+    /// The center of action button `index`, in window coordinates.
+    fn button_center(overlay: &Overlay, index: usize) -> (f32, f32) {
+        let rect = overlay.card_layout.actions[index];
+        let inset = overlay.theme.inset();
+        (
+            inset + rect.x + rect.width / 2.0,
+            inset + rect.y + rect.height / 2.0,
+        )
+    }
+
+    #[test]
+    fn runs_the_clicked_action() {
+        let mut overlay =
+            Overlay::new(bundled_font_system(), SizeLimits::default(), Theme::light());
+        overlay.set_card(Card::text("hello"));
+        let (x, y) = button_center(&overlay, 1);
+        assert!(overlay.pointer_moved(x, y));
+        assert!(overlay.pointer_pressed());
+        assert_eq!(overlay.pointer_released(), Some(Command::Pin));
+
+        // the corner of the window is outside the panel
+        assert!(overlay.pointer_moved(0.0, 0.0));
+        assert!(!overlay.pointer_pressed());
+        assert_eq!(overlay.pointer_released(), None);
+    }
+
+    #[test]
+    fn runs_the_focused_action() {
+        let mut overlay =
+            Overlay::new(bundled_font_system(), SizeLimits::default(), Theme::light());
+        overlay.set_card(Card::text("hello"));
+        assert_eq!(overlay.activate(), Some(Command::Copy("hello".to_owned())));
+        overlay.focus_next(true);
+        assert_eq!(overlay.activate(), Some(Command::Pin));
+    }
+
+    #[test]
+    fn scrolls_within_the_content() {
+        let mut overlay =
+            Overlay::new(bundled_font_system(), SizeLimits::default(), Theme::light());
+        let long = (1..=100)
+            .map(|line| format!("line {line}\n"))
+            .collect::<String>();
+        overlay.set_card(Card::code(&long, Language::Rust));
+        let content_height = layout::measure(&overlay.body_buffer).height;
+        let viewport_height = overlay.card_layout.body.unwrap().height;
+        assert_eq!(overlay.max_scroll, content_height - viewport_height);
+        assert!(overlay.max_scroll > 0.0);
+
+        assert!(!overlay.scroll_by(-10.0));
+        assert!(overlay.scroll_by(overlay.line_height()));
+        assert_eq!(overlay.scroll, overlay.line_height());
+        assert!(overlay.scroll_by(1e6));
+        assert_eq!(overlay.scroll, overlay.max_scroll);
+        assert!(!overlay.scroll_by(1.0));
+        assert!(overlay.scroll_by(-1e6));
+        assert_eq!(overlay.scroll, 0.0);
+
+        // re-laying out the card keeps the position, a new one starts over
+        overlay.scroll_by(50.0);
+        overlay.set_theme(Theme::light());
+        assert_eq!(overlay.scroll, 50.0);
+        overlay.set_card(Card::text("short"));
+        assert_eq!(overlay.scroll, 0.0);
+        assert_eq!(overlay.max_scroll, 0.0);
+        assert!(!overlay.scroll_by(10.0));
+    }
+}
@@ -0,0 +1,131 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::card::Card;
+use crate::highlight::Language;
+
+/// A capture pinned to the screen in a window of its own.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Pin {
+    pub text: String,
+    pub language: Option<Language>,
+    /// Physical position of the window, once it has one.
+    pub position: Option<(i32, i32)>,
+}
+
+impl Pin {
+    /// Pins `card` in a window at `position`.
+    pub fn new(card: &Card, position: Option<(i32, i32)>) -> Self {
+        Self {
+            text: card.body.clone(),
+            language: card.language,
+            position,
+        }
+    }
+
+    pub fn card(&self) -> Card {
+        match self.language {
+            Some(language) => Card::code(&self.text, language),
+            None => Card::text(&self.text),
+        }
+        .pinned()
+    }
+}
+
+/// The pins that are restored on the next start.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Pins {
+    #[serde(default)]
+    pub pins: Vec<Pin>,
+}
+
+impl Pins {
+    pub fn path() -> Option<PathBuf> {
+        Some(
+            dirs::data_dir()?
+                .join(env!("CARGO_PKG_NAME"))
+                .join("pins.toml"),
+        )
+    }
+
+    /// Loads the pins saved at `path`, or none if nothing was saved.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read {}", path.display()));
+            }
+        };
+        toml::from_str(&contents).with_context(|| format!("failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        let contents = toml::to_string(self).context("failed to serialize pins")?;
+        fs::write(path, contents).with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::Command;
+
+    #[test]
+    fn pins_cards_as_they_were_shown() {
+        let card = Card::code("fn main() {}", Language::Rust);
+        let pin = Pin::new(&card, Some((10, -20)));
+        let pinned = pin.card();
+        assert_eq!(pinned.title, "Rust");
+        assert_eq!(pinned.body, card.body);
+        assert_eq!(
+            pinned
+                .actions
+                .iter()
+                .map(|action| &action.command)
+                .collect::<Vec<_>>(),
+            [&Command::Copy("fn main() {}".to_owned()), &Command::Dismiss]
+        );
+        assert_eq!(
+            Pin::new(&Card::text("hello"), None).card().title,
+            "Selection"
+        );
+    }
+
+    #[test]
+    fn saves_and_restores_pins() {
+        let dir = std::env::temp_dir().join(format!(
+            "{}-pins-{}",
+            env!("CARGO_PKG_NAME"),
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("pins.toml");
+        assert!(Pins::load(&path).unwrap().pins.is_empty());
+
+        let pins = Pins {
+            pins: vec![
+                Pin::new(&Card::text("first"), Some((10, 20))),
+                Pin::new(&Card::code("second", Language::Go), None),
+            ],
+        };
+        pins.save(&path).unwrap();
+        assert_eq!(Pins::load(&path).unwrap().pins, pins.pins);
+
+        // closing every pin leaves nothing to restore
+        Pins::default().save(&path).unwrap();
+        assert!(Pins::load(&path).unwrap().pins.is_empty());
+
+        fs::write(&path, "pins = 1").unwrap();
+        assert!(Pins::load(&path).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
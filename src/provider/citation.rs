@@ -0,0 +1,108 @@
+use super::link::is_url;
+use super::{ContextProvider, Suggestion};
+use crate::capture::Capture;
+use crate::card::{Action, Card, Command};
+use crate::render::Icon;
+
+/// Quotes selections made on web pages along with where they're from.
+pub struct CitationProvider;
+
+impl ContextProvider for CitationProvider {
+    fn name(&self) -> &str {
+        "citation"
+    }
+
+    fn provide(&self, capture: &Capture) -> anyhow::Result<Vec<Suggestion>> {
+        let Some(page) = &capture.page else {
+            return Ok(Vec::new());
+        };
+        let Some(url) = &page.url else {
+            return Ok(Vec::new());
+        };
+        let source = match &page.title {
+            Some(title) => format!("{title}, {url}"),
+            None => url.clone(),
+        };
+        let citation = format!("“{}”\n— {source}", capture.text.trim());
+        let mut actions = vec![Action {
+            label: "Copy".to_owned(),
+            command: Command::Copy(citation.clone()),
+        }];
+        // pages can be at URLs that run scripts or open other apps
+        if is_url(url) {
+            actions.push(Action {
+                label: "Open".to_owned(),
+                command: Command::Open(url.clone()),
+            });
+        }
+
+        Ok(vec![Suggestion {
+            card: Card {
+                icon: Some(Icon::Info),
+                title: "Quote".to_owned(),
+                body: citation,
+                language: None,
+                image: None,
+                actions,
+                footer: Card::FOOTER.to_owned(),
+            },
+            score: 0.5,
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::{Page, Trigger};
+
+    fn on_page(url: &str, title: Option<&str>) -> Capture {
+        Capture {
+            page: Some(Page {
+                url: Some(url.to_owned()),
+                title: title.map(str::to_owned),
+                link: None,
+            }),
+            ..Capture::new(" to be or not to be ".to_owned(), Trigger::Poll)
+        }
+    }
+
+    #[test]
+    fn quotes_selections_with_their_page() {
+        let capture = on_page("https://example.com/hamlet", Some("Hamlet"));
+        let card = &CitationProvider.provide(&capture).unwrap()[0].card;
+        let citation = "“to be or not to be”\n— Hamlet, https://example.com/hamlet";
+        assert_eq!(card.body, citation);
+        assert_eq!(
+            card.actions
+                .iter()
+                .map(|action| &action.command)
+                .collect::<Vec<_>>(),
+            [
+                &Command::Copy(citation.to_owned()),
+                &Command::Open("https://example.com/hamlet".to_owned())
+            ]
+        );
+
+        let capture = on_page("https://example.com/", None);
+        let card = &CitationProvider.provide(&capture).unwrap()[0].card;
+        assert_eq!(card.body, "“to be or not to be”\n— https://example.com/");
+    }
+
+    #[test]
+    fn opens_only_web_pages() {
+        let capture = on_page("file:///Users/someone/hamlet.html", None);
+        let card = &CitationProvider.provide(&capture).unwrap()[0].card;
+        assert!(
+            card.actions
+                .iter()
+                .all(|action| !matches!(action.command, Command::Open(_)))
+        );
+    }
+
+    #[test]
+    fn ignores_selections_outside_web_pages() {
+        let capture = Capture::new("hello".to_owned(), Trigger::Poll);
+        assert_eq!(CitationProvider.provide(&capture).unwrap(), []);
+    }
+}
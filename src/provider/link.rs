@@ -0,0 +1,114 @@
+use super::{ContextProvider, Suggestion};
+use crate::capture::Capture;
+use crate::card::{Action, Card, Command};
+use crate::render::Icon;
+
+/// Offers to open web URLs that are selected, or that the selected link
+/// points to.
+pub struct LinkProvider;
+
+impl ContextProvider for LinkProvider {
+    fn name(&self) -> &str {
+        "link"
+    }
+
+    fn provide(&self, capture: &Capture) -> anyhow::Result<Vec<Suggestion>> {
+        let text = capture.text.trim();
+        let (url, score) = if is_url(text) {
+            (text, 0.9)
+        } else if let Some(link) = capture
+            .page
+            .as_ref()
+            .and_then(|page| page.link.as_deref())
+            // links can run scripts or open other apps, so only web pages
+            // are offered
+            .filter(|link| is_url(link))
+        {
+            (link, 0.6)
+        } else {
+            return Ok(Vec::new());
+        };
+
+        Ok(vec![Suggestion {
+            card: Card {
+                icon: Some(Icon::Link),
+                title: host(url).unwrap_or("Link").to_owned(),
+                body: url.to_owned(),
+                language: None,
+                image: None,
+                actions: vec![
+                    Action {
+                        label: "Open".to_owned(),
+                        command: Command::Open(url.to_owned()),
+                    },
+                    Action {
+                        label: "Copy".to_owned(),
+                        command: Command::Copy(url.to_owned()),
+                    },
+                ],
+                footer: Card::FOOTER.to_owned(),
+            },
+            score,
+        }])
+    }
+}
+
+/// Whether `text` is a web URL, the only kind offered to be opened.
+pub(super) fn is_url(text: &str) -> bool {
+    (text.starts_with("https://") || text.starts_with("http://"))
+        && !text.contains(char::is_whitespace)
+}
+
+/// The host of `url`, like `example.com`.
+fn host(url: &str) -> Option<&str> {
+    let (_, rest) = url.split_once("://")?;
+    let host = rest.split(['/', '?', '#']).next()?;
+    (!host.is_empty()).then_some(host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::{Page, Trigger};
+
+    fn in_link(link: &str) -> Capture {
+        Capture {
+            page: Some(Page {
+                url: Some("https://example.com/".to_owned()),
+                title: None,
+                link: Some(link.to_owned()),
+            }),
+            ..Capture::new("click here".to_owned(), Trigger::Poll)
+        }
+    }
+
+    #[test]
+    fn offers_selected_urls() {
+        let capture = Capture::new(" https://example.com/a?b ".to_owned(), Trigger::Poll);
+        let suggestions = LinkProvider.provide(&capture).unwrap();
+        assert_eq!(suggestions[0].card.title, "example.com");
+        assert_eq!(suggestions[0].score, 0.9);
+
+        let capture = Capture::new("example.com".to_owned(), Trigger::Poll);
+        assert_eq!(LinkProvider.provide(&capture).unwrap(), []);
+    }
+
+    #[test]
+    fn offers_only_web_links() {
+        let suggestions = LinkProvider
+            .provide(&in_link("http://example.com/"))
+            .unwrap();
+        assert_eq!(
+            suggestions[0].card.actions[0].command,
+            Command::Open("http://example.com/".to_owned())
+        );
+        for link in [
+            "javascript:alert(1)",
+            "file:///etc/passwd",
+            "mailto:someone@example.com",
+            "vscode://file/tmp",
+        ] {
+            assert_eq!(LinkProvider.provide(&in_link(link)).unwrap(), [], "{link}");
+        }
+    }
+}
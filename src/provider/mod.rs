@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use winit::event_loop::EventLoopProxy;
+
+use self::citation::CitationProvider;
+use self::link::LinkProvider;
+use self::preview::PreviewProvider;
+use crate::capture::Capture;
+use crate::card::Card;
+use crate::event::Event;
+
+mod citation;
+mod link;
+mod preview;
+
+/// Turns a capture into cards with something useful about it.
+pub trait ContextProvider: Send + Sync {
+    /// Identifies the provider in the config file and log messages.
+    fn name(&self) -> &str;
+
+    fn provide(&self, capture: &Capture) -> anyhow::Result<Vec<Suggestion>>;
+}
+
+/// A card and how relevant it is, from 0 to 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    pub card: Card,
+    pub score: f32,
+}
+
+/// Which providers run and how long they get.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ProviderConfig {
+    pub timeout_ms: u64,
+    /// Timeouts of providers that need more or less time, by name.
+    pub timeouts: HashMap<String, u64>,
+    /// Names of the providers that don't run.
+    pub disabled: Vec<String>,
+}
+
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 300,
+            timeouts: HashMap::new(),
+            disabled: Vec::new(),
+        }
+    }
+}
+
+/// What a provider came up with, by its index in the registry.
+type Outcome = (usize, anyhow::Result<Vec<Suggestion>>);
+
+/// A provider with the thread it runs on, which takes one capture at a time,
+/// so one that hangs holds up nothing but itself.
+struct Worker {
+    provider: Arc<dyn ContextProvider>,
+    timeout: Duration,
+    busy: Arc<AtomicBool>,
+    job_tx: mpsc::Sender<(Capture, mpsc::Sender<Outcome>)>,
+}
+
+/// The providers that are run on every capture shown in the overlay.
+pub struct Registry {
+    workers: Vec<Worker>,
+}
+
+impl Registry {
+    /// A registry of the built-in providers that aren't disabled.
+    pub fn new(config: &ProviderConfig) -> Self {
+        let mut registry = Self {
+            workers: Vec::new(),
+        };
+        let providers: [Arc<dyn ContextProvider>; 3] = [
+            Arc::new(LinkProvider),
+            Arc::new(CitationProvider),
+            Arc::new(PreviewProvider),
+        ];
+        for provider in providers {
+            if config.disabled.iter().any(|name| name == provider.name()) {
+                continue;
+            }
+            let timeout = config
+                .timeouts
+                .get(provider.name())
+                .copied()
+                .unwrap_or(config.timeout_ms);
+            registry.register(provider, Duration::from_millis(timeout));
+        }
+        registry
+    }
+
+    /// Adds `provider`, starting the thread it runs on, which stops when the
+    /// registry is dropped.
+    pub fn register(&mut self, provider: Arc<dyn ContextProvider>, timeout: Duration) {
+        let index = self.workers.len();
+        let busy = Arc::new(AtomicBool::new(false));
+        let (job_tx, job_rx) = mpsc::channel::<(Capture, mpsc::Sender<Outcome>)>();
+        thread::spawn({
+            let (provider, busy) = (provider.clone(), busy.clone());
+            move || {
+                while let Ok((capture, result_tx)) = job_rx.recv() {
+                    let result = provider.provide(&capture);
+                    // free before reporting, so the next run doesn't find it
+                    // busy with a capture it's done with
+                    busy.store(false, Ordering::Release);
+                    let _ = result_tx.send((index, result));
+                }
+            }
+        });
+        self.workers.push(Worker {
+            provider,
+            timeout,
+            busy,
+            job_tx,
+        });
+    }
+
+    /// Runs every provider on `capture` at once, each on its own thread, and
+    /// returns the cards of those that finished in time, best first.
+    /// Providers still busy with an earlier capture are skipped.
+    pub fn run(&self, capture: &Capture) -> Vec<Card> {
+        let start = Instant::now();
+        let (result_tx, result_rx) = mpsc::channel();
+        let mut pending = Vec::new();
+        for (index, worker) in self.workers.iter().enumerate() {
+            if worker.busy.swap(true, Ordering::Acquire) {
+                eprintln!("provider {} is still busy", worker.provider.name());
+                continue;
+            }
+            if worker
+                .job_tx
+                .send((capture.clone(), result_tx.clone()))
+                .is_err()
+            {
+                eprintln!("provider {} stopped", worker.provider.name());
+                continue;
+            }
+            pending.push(index);
+        }
+        drop(result_tx);
+
+        let deadline = |index: usize| start + self.workers[index].timeout;
+        let mut suggestions = Vec::new();
+        // providers that time out are left to finish on their own, and what
+        // they come up with is dropped along with the channel
+        while let Some(last) = pending.iter().map(|&index| deadline(index)).max() {
+            let Ok((index, result)) =
+                result_rx.recv_timeout(last.saturating_duration_since(Instant::now()))
+            else {
+                break;
+            };
+            pending.retain(|&pending| pending != index);
+            let name = self.workers[index].provider.name();
+            match result {
+                Ok(_) if Instant::now() > deadline(index) => {
+                    eprintln!("provider {name} took too long");
+                }
+                Ok(found) => suggestions.extend(found.into_iter().map(|found| (index, found))),
+                Err(err) => eprintln!("provider {name} failed: {err:#}"),
+            }
+        }
+        for index in pending {
+            eprintln!(
+                "provider {} took too long",
+                self.workers[index].provider.name()
+            );
+        }
+
+        // ties go to the provider registered first
+        suggestions.sort_by(|(a_index, a), (b_index, b)| {
+            b.score.total_cmp(&a.score).then(a_index.cmp(b_index))
+        });
+        suggestions
+            .into_iter()
+            .map(|(_, suggestion)| suggestion.card)
+            .collect()
+    }
+}
+
+/// Runs the providers of `registry` on captures sent to the returned channel
+/// with the generation of the selection they're for, sending back the cards
+/// they come up with. Captures that were superseded while the providers were
+/// busy are skipped.
+pub fn spawn(
+    registry: Registry,
+    event_tx: mpsc::Sender<Event>,
+    proxy: EventLoopProxy,
+) -> mpsc::Sender<(u64, Capture)> {
+    let (capture_tx, capture_rx) = mpsc::channel::<(u64, Capture)>();
+    thread::spawn(move || {
+        while let Ok(latest) = capture_rx.recv() {
+            let (generation, capture) = capture_rx.try_iter().last().unwrap_or(latest);
+            let cards = registry.run(&capture);
+            if cards.is_empty() {
+                continue;
+            }
+            if event_tx.send(Event::Cards { generation, cards }).is_err() {
+                return;
+            }
+            proxy.wake_up();
+        }
+    });
+    capture_tx
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+    use crate::capture::Trigger;
+
+    /// A provider that doesn't return until it's let go.
+    struct Hanging {
+        calls: AtomicUsize,
+        release: Mutex<mpsc::Receiver<()>>,
+    }
+
+    impl ContextProvider for Hanging {
+        fn name(&self) -> &str {
+            "hanging"
+        }
+
+        fn provide(&self, _: &Capture) -> anyhow::Result<Vec<Suggestion>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let _ = self.release.lock().unwrap().recv();
+            Ok(Vec::new())
+        }
+    }
+
+    /// A provider that takes `delay` to suggest cards with `scores`, titled
+    /// after it.
+    struct Stub {
+        name: &'static str,
+        delay: Duration,
+        scores: Vec<f32>,
+    }
+
+    impl ContextProvider for Stub {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn provide(&self, _: &Capture) -> anyhow::Result<Vec<Suggestion>> {
+            thread::sleep(self.delay);
+            Ok(self
+                .scores
+                .iter()
+                .map(|&score| Suggestion {
+                    card: Card {
+                        title: format!("{} {score}", self.name),
+                        ..Card::text("")
+                    },
+                    score,
+                })
+                .collect())
+        }
+    }
+
+    fn registry(stubs: impl IntoIterator<Item = (Stub, Duration)>) -> Registry {
+        let mut registry = Registry {
+            workers: Vec::new(),
+        };
+        for (stub, timeout) in stubs {
+            registry.register(Arc::new(stub), timeout);
+        }
+        registry
+    }
+
+    fn titles(cards: &[Card]) -> Vec<&str> {
+        cards.iter().map(|card| card.title.as_str()).collect()
+    }
+
+    #[test]
+    fn ranks_cards_by_score_then_provider() {
+        let stub = |name, scores| Stub {
+            name,
+            delay: Duration::ZERO,
+            scores,
+        };
+        let timeout = Duration::from_secs(5);
+        let registry = registry([
+            (stub("a", vec![0.5, 0.2]), timeout),
+            (stub("b", vec![0.9, 0.5]), timeout),
+        ]);
+        let cards = registry.run(&Capture::new("hello".to_owned(), Trigger::Poll));
+        assert_eq!(titles(&cards), ["b 0.9", "a 0.5", "b 0.5", "a 0.2"]);
+    }
+
+    #[test]
+    fn drops_cards_of_providers_that_take_too_long() {
+        let stub = |name, delay| Stub {
+            name,
+            delay: Duration::from_millis(delay),
+            scores: vec![1.0],
+        };
+        // each provider gets its own timeout
+        let registry = registry([
+            (stub("slow", 200), Duration::from_millis(20)),
+            (stub("patient", 50), Duration::from_secs(5)),
+        ]);
+        let start = Instant::now();
+        let cards = registry.run(&Capture::new("hello".to_owned(), Trigger::Poll));
+        assert_eq!(titles(&cards), ["patient 1"]);
+        assert!(start.elapsed() < Duration::from_millis(200));
+    }
+
+    #[test]
+    fn skips_providers_still_busy() {
+        let (release_tx, release_rx) = mpsc::channel();
+        let hanging = Arc::new(Hanging {
+            calls: AtomicUsize::new(0),
+            release: Mutex::new(release_rx),
+        });
+        let mut registry = Registry {
+            workers: Vec::new(),
+        };
+        registry.register(hanging.clone(), Duration::from_millis(10));
+        let capture = Capture::new("hello".to_owned(), Trigger::Poll);
+
+        assert_eq!(registry.run(&capture), []);
+        assert_eq!(registry.run(&capture), []);
+        assert_eq!(hanging.calls.load(Ordering::SeqCst), 1);
+
+        // once it's done, it takes the next capture
+        release_tx.send(()).unwrap();
+        while registry.workers[0].busy.load(Ordering::Acquire) {
+            thread::yield_now();
+        }
+        release_tx.send(()).unwrap();
+        assert_eq!(registry.run(&capture), []);
+        assert_eq!(hanging.calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,89 @@
+use std::path::Path;
+
+use super::{ContextProvider, Suggestion};
+use crate::capture::Capture;
+use crate::card::{Action, Card, Command};
+use crate::render::{Icon, Image};
+
+/// Largest side of image thumbnails, in pixels.
+const THUMBNAIL_SIZE: u32 = 256;
+
+/// Shows a thumbnail of PNGs whose path is selected. Decoding them happens
+/// here rather than in the selection card so it stays off the event loop.
+pub struct PreviewProvider;
+
+impl ContextProvider for PreviewProvider {
+    fn name(&self) -> &str {
+        "preview"
+    }
+
+    fn provide(&self, capture: &Capture) -> anyhow::Result<Vec<Suggestion>> {
+        let text = capture.text.trim();
+        let path = Path::new(text);
+        let is_png = path.is_absolute()
+            && path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+        if !is_png || !path.is_file() {
+            return Ok(Vec::new());
+        }
+        let image = Image::load_png(path, THUMBNAIL_SIZE)?;
+
+        Ok(vec![Suggestion {
+            card: Card {
+                icon: Some(Icon::Info),
+                title: path.file_name().map_or_else(
+                    || "Image".to_owned(),
+                    |name| name.to_string_lossy().into_owned(),
+                ),
+                body: text.to_owned(),
+                language: None,
+                image: Some(image),
+                actions: vec![
+                    Action {
+                        label: "Open".to_owned(),
+                        command: Command::Open(text.to_owned()),
+                    },
+                    Action {
+                        label: "Copy".to_owned(),
+                        command: Command::Copy(text.to_owned()),
+                    },
+                ],
+                footer: Card::FOOTER.to_owned(),
+            },
+            score: 0.8,
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::capture::Trigger;
+
+    fn provide(text: &str) -> Vec<Suggestion> {
+        let capture = Capture::new(text.to_owned(), Trigger::Poll);
+        PreviewProvider.provide(&capture).unwrap()
+    }
+
+    #[test]
+    fn shows_thumbnails_of_selected_pngs() {
+        let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "snapshots", "text.png"]
+            .iter()
+            .collect();
+        let suggestions = provide(&format!(" {}\n", path.display()));
+        let card = &suggestions[0].card;
+        assert_eq!(card.title, "text.png");
+        let image = card.image.as_ref().unwrap();
+        assert!(image.width().max(image.height()) <= THUMBNAIL_SIZE);
+    }
+
+    #[test]
+    fn ignores_other_paths() {
+        assert_eq!(provide("tests/snapshots/text.png"), []);
+        assert_eq!(provide("/no/such/image.png"), []);
+        assert_eq!(provide(env!("CARGO_MANIFEST_DIR")), []);
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/// What the glyph atlas holds, estimated from the glyphs prepared.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AtlasStats {
+    /// Glyphs used in the last frame, which the atlas can't evict.
+    pub glyphs: usize,
+    pub bytes: usize,
+    /// Bytes of every glyph uploaded since the atlas was last rebuilt, which
+    /// its textures have grown to hold.
+    pub allocated: usize,
+    /// How often the atlas went over budget.
+    pub rebuilds: usize,
+}
+
+/// Keeps track of the glyphs uploaded to an atlas, which only grows until
+/// it's rebuilt, and of those the current frame uses, which it can't evict.
+#[derive(Debug)]
+pub struct AtlasUsage<K> {
+    /// Roughly how much glyph data the atlas may hold before it's rebuilt
+    /// from scratch, in bytes.
+    budget: usize,
+    /// Bytes of each glyph uploaded since the atlas was last rebuilt.
+    allocated: HashMap<K, usize>,
+    /// Glyphs used in the current frame.
+    frame: HashSet<K>,
+    stats: AtlasStats,
+}
+
+impl<K: Copy + Eq + Hash> AtlasUsage<K> {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            allocated: HashMap::new(),
+            frame: HashSet::new(),
+            stats: AtlasStats::default(),
+        }
+    }
+
+    /// Starts a frame; the atlas was trimmed after the last one, so only the
+    /// glyphs used from now on are in use.
+    pub fn begin_frame(&mut self) {
+        self.frame.clear();
+        self.stats.glyphs = 0;
+        self.stats.bytes = 0;
+    }
+
+    /// Accounts for a glyph the frame uses, and as uploaded if it isn't in
+    /// the atlas yet, asking `bytes` how much it takes up.
+    pub fn use_glyph(&mut self, key: K, bytes: impl FnOnce() -> usize) {
+        if !self.frame.insert(key) {
+            return;
+        }
+        let bytes = *self.allocated.entry(key).or_insert_with(|| {
+            let bytes = bytes();
+            self.stats.allocated += bytes;
+            bytes
+        });
+        self.stats.glyphs += 1;
+        self.stats.bytes += bytes;
+    }
+
+    pub fn over_budget(&self) -> bool {
+        self.stats.allocated > self.budget
+    }
+
+    /// Forgets every glyph, after the atlas was replaced by an empty one.
+    pub fn rebuilt(&mut self) {
+        self.allocated.clear();
+        self.frame.clear();
+        self.stats = AtlasStats {
+            rebuilds: self.stats.rebuilds + 1,
+            ..AtlasStats::default()
+        };
+    }
+
+    pub fn stats(&self) -> AtlasStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(usage: &mut AtlasUsage<char>, glyphs: &str) {
+        usage.begin_frame();
+        for glyph in glyphs.chars() {
+            usage.use_glyph(glyph, || 100);
+        }
+    }
+
+    #[test]
+    fn allocates_each_glyph_once() {
+        let mut usage = AtlasUsage::new(1000);
+        frame(&mut usage, "abba");
+        assert_eq!(
+            usage.stats(),
+            AtlasStats {
+                glyphs: 2,
+                bytes: 200,
+                allocated: 200,
+                rebuilds: 0,
+            }
+        );
+
+        usage.begin_frame();
+        usage.use_glyph('a', || unreachable!("already uploaded"));
+        assert_eq!(usage.stats().allocated, 200);
+    }
+
+    #[test]
+    fn counts_only_the_glyphs_of_the_current_frame_as_in_use() {
+        let mut usage = AtlasUsage::new(1000);
+        frame(&mut usage, "abc");
+        frame(&mut usage, "c");
+        let stats = usage.stats();
+        assert_eq!((stats.glyphs, stats.bytes), (1, 100));
+        // evictable glyphs still take up the space the atlas grew to
+        assert_eq!(stats.allocated, 300);
+    }
+
+    #[test]
+    fn grows_until_over_budget_then_starts_over() {
+        let mut usage = AtlasUsage::new(250);
+        frame(&mut usage, "ab");
+        assert!(!usage.over_budget());
+        frame(&mut usage, "c");
+        assert!(usage.over_budget());
+
+        usage.rebuilt();
+        assert_eq!(
+            usage.stats(),
+            AtlasStats {
+                rebuilds: 1,
+                ..AtlasStats::default()
+            }
+        );
+        frame(&mut usage, "c");
+        assert_eq!(usage.stats().allocated, 100);
+        assert!(!usage.over_budget());
+    }
+}
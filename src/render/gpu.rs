@@ -0,0 +1,279 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use wgpu::InstanceDescriptor;
+use winit::dpi::PhysicalSize;
+use winit::window::Window;
+
+use super::atlas::{AtlasStats, AtlasUsage};
+use super::image::ImagePipeline;
+use super::panel::PanelPipeline;
+use super::{Renderer, Scene, icon};
+
+/// Roughly how much glyph data the atlas may hold before it's rebuilt from
+/// scratch, in bytes.
+const ATLAS_BUDGET: usize = 8 * 1024 * 1024;
+
+pub struct GpuRenderer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    surface: wgpu::Surface<'static>,
+    surface_config: wgpu::SurfaceConfiguration,
+
+    swash_cache: glyphon::SwashCache,
+    cache: glyphon::Cache,
+    viewport: glyphon::Viewport,
+    atlas: glyphon::TextAtlas,
+    text_renderer: glyphon::TextRenderer,
+    atlas_usage: AtlasUsage<glyphon::CacheKey>,
+    panel_pipeline: PanelPipeline,
+    image_pipeline: ImagePipeline,
+
+    window: Arc<dyn Window>,
+}
+
+impl GpuRenderer {
+    pub async fn new(window: Arc<dyn Window>) -> anyhow::Result<Self> {
+        let physical_size = window.surface_size();
+
+        // initialize wgpu
+        let instance = wgpu::Instance::new(&InstanceDescriptor::default());
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions::default())
+            .await
+            .context("failed to find an adapter")?;
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor::default())
+            .await
+            .context("failed to request device")?;
+
+        // configure surface
+        let surface = instance
+            .create_surface(window.clone())
+            .context("failed to create surface")?;
+        let swapchain_format = wgpu::TextureFormat::Bgra8UnormSrgb;
+        // the panel is drawn with premultiplied alpha so the corners and the
+        // shadow blend with whatever is behind the window
+        let alpha_modes = surface.get_capabilities(&adapter).alpha_modes;
+        let alpha_mode = [
+            wgpu::CompositeAlphaMode::PreMultiplied,
+            wgpu::CompositeAlphaMode::PostMultiplied,
+        ]
+        .into_iter()
+        .find(|mode| alpha_modes.contains(mode))
+        .unwrap_or(wgpu::CompositeAlphaMode::Auto);
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: swapchain_format,
+            width: physical_size.width,
+            height: physical_size.height,
+            present_mode: wgpu::PresentMode::AutoVsync,
+            alpha_mode,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        surface.configure(&device, &surface_config);
+
+        // text renderer
+        let swash_cache = glyphon::SwashCache::new();
+        let cache = glyphon::Cache::new(&device);
+        let viewport = glyphon::Viewport::new(&device, &cache);
+        let mut atlas = glyphon::TextAtlas::new(&device, &queue, &cache, swapchain_format);
+        let text_renderer = glyphon::TextRenderer::new(
+            &mut atlas,
+            &device,
+            wgpu::MultisampleState::default(),
+            None,
+        );
+        let panel_pipeline = PanelPipeline::new(&device, swapchain_format);
+        let image_pipeline = ImagePipeline::new(&device, swapchain_format);
+
+        Ok(Self {
+            device,
+            queue,
+            surface,
+            surface_config,
+            swash_cache,
+            cache,
+            viewport,
+            atlas,
+            text_renderer,
+            atlas_usage: AtlasUsage::new(ATLAS_BUDGET),
+            panel_pipeline,
+            image_pipeline,
+            window,
+        })
+    }
+
+    /// Accounts for the glyphs of `text_areas`, and for those that aren't in
+    /// the atlas yet as uploaded.
+    fn track_glyphs(
+        &mut self,
+        font_system: &mut glyphon::FontSystem,
+        text_areas: &[glyphon::TextArea<'_>],
+    ) {
+        for area in text_areas {
+            for run in area.buffer.layout_runs() {
+                for glyph in run.glyphs {
+                    let key = glyph.physical((area.left, area.top), area.scale).cache_key;
+                    let swash_cache = &mut self.swash_cache;
+                    self.atlas_usage.use_glyph(key, || {
+                        swash_cache
+                            .get_image(font_system, key)
+                            .as_ref()
+                            .map_or(0, glyph_bytes)
+                    });
+                }
+            }
+        }
+    }
+
+    pub fn atlas_stats(&self) -> AtlasStats {
+        self.atlas_usage.stats()
+    }
+
+    /// Drops every cached glyph, freeing the atlas textures and the rasterized
+    /// glyphs they were uploaded from.
+    fn rebuild_atlas(&mut self) {
+        let format = self.surface_config.format;
+        self.atlas = glyphon::TextAtlas::new(&self.device, &self.queue, &self.cache, format);
+        self.text_renderer = glyphon::TextRenderer::new(
+            &mut self.atlas,
+            &self.device,
+            wgpu::MultisampleState::default(),
+            None,
+        );
+        self.swash_cache = glyphon::SwashCache::new();
+        self.atlas_usage.rebuilt();
+    }
+}
+
+impl Renderer for GpuRenderer {
+    fn resize(&mut self, size: PhysicalSize<u32>) -> anyhow::Result<()> {
+        self.surface_config.width = size.width;
+        self.surface_config.height = size.height;
+        self.surface.configure(&self.device, &self.surface_config);
+        Ok(())
+    }
+
+    fn render(
+        &mut self,
+        font_system: &mut glyphon::FontSystem,
+        scene: Scene<'_>,
+    ) -> anyhow::Result<()> {
+        self.atlas_usage.begin_frame();
+        self.track_glyphs(font_system, &scene.text_areas);
+        if self.atlas_usage.over_budget() {
+            let AtlasStats {
+                glyphs,
+                bytes,
+                allocated,
+                rebuilds,
+            } = self.atlas_stats();
+            eprintln!(
+                "glyph atlas over budget with {allocated} bytes uploaded and {glyphs} glyphs in \
+                 {bytes} bytes in use, rebuilding ({rebuilds} times before)"
+            );
+            self.rebuild_atlas();
+            self.track_glyphs(font_system, &scene.text_areas);
+        }
+
+        self.viewport.update(
+            &self.queue,
+            glyphon::Resolution {
+                width: self.surface_config.width,
+                height: self.surface_config.height,
+            },
+        );
+        self.text_renderer.prepare_with_custom(
+            &self.device,
+            &self.queue,
+            font_system,
+            &mut self.atlas,
+            &self.viewport,
+            scene.text_areas,
+            &mut self.swash_cache,
+            icon::rasterize,
+        )?;
+        let resolution = [
+            self.surface_config.width as f32,
+            self.surface_config.height as f32,
+        ];
+        self.panel_pipeline
+            .prepare(&self.device, &self.queue, resolution, &scene.panels);
+        self.image_pipeline
+            .prepare(&self.device, &self.queue, resolution, &scene.images);
+
+        let frame = self.surface.get_current_texture()?;
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(premultiplied(to_linear(scene.background))),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            self.panel_pipeline.render(&mut pass);
+            self.image_pipeline.render(&mut pass);
+            self.text_renderer
+                .render(&self.atlas, &self.viewport, &mut pass)?;
+        }
+
+        self.queue.submit(Some(encoder.finish()));
+        self.window.pre_present_notify();
+        frame.present();
+
+        // lets glyphs that weren't used this frame be evicted once it fills
+        self.atlas.trim();
+
+        Ok(())
+    }
+}
+
+/// How much of the atlas `image` takes up.
+fn glyph_bytes(image: &glyphon::SwashImage) -> usize {
+    let bytes_per_pixel = match image.content {
+        glyphon::SwashContent::Mask => 1,
+        _ => 4,
+    };
+    image.placement.width as usize * image.placement.height as usize * bytes_per_pixel
+}
+
+/// Converts an sRGB color into the linear color the sRGB swapchain expects.
+pub(super) fn to_linear(color: glyphon::Color) -> wgpu::Color {
+    let channel = |value: u8| {
+        let value = value as f64 / 255.0;
+        if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    wgpu::Color {
+        r: channel(color.r()),
+        g: channel(color.g()),
+        b: channel(color.b()),
+        a: color.a() as f64 / 255.0,
+    }
+}
+
+pub(super) fn premultiplied(color: wgpu::Color) -> wgpu::Color {
+    wgpu::Color {
+        r: color.r * color.a,
+        g: color.g * color.a,
+        b: color.b * color.a,
+        a: color.a,
+    }
+}
@@ -0,0 +1,87 @@
+use glyphon::{ContentType, CustomGlyphId, RasterizeCustomGlyphRequest, RasterizedCustomGlyph};
+
+/// Icons drawn as glyphon custom glyphs, rasterized from signed distance
+/// functions so they stay crisp at any scale.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Icon {
+    Text,
+    Link,
+    Code,
+    Info,
+}
+
+impl Icon {
+    const ALL: [Self; 4] = [Self::Text, Self::Link, Self::Code, Self::Info];
+    /// Stroke width in the unit square the icons are drawn in.
+    const STROKE: f32 = 0.09;
+
+    pub fn id(self) -> CustomGlyphId {
+        self as CustomGlyphId
+    }
+
+    pub fn from_id(id: CustomGlyphId) -> Option<Self> {
+        Self::ALL.into_iter().find(|icon| icon.id() == id)
+    }
+
+    /// Distance from (`x`, `y`) in the unit square to the nearest stroke.
+    fn distance(self, x: f32, y: f32) -> f32 {
+        let p = (x, y);
+        let distance = match self {
+            Self::Text => [(0.15, 0.25, 0.85), (0.15, 0.5, 0.85), (0.15, 0.75, 0.6)]
+                .into_iter()
+                .map(|(x0, y, x1)| segment(p, (x0, y), (x1, y)))
+                .fold(f32::INFINITY, f32::min),
+            Self::Link => ring(p, (0.36, 0.5), 0.2).min(ring(p, (0.64, 0.5), 0.2)),
+            Self::Code => [
+                ((0.3, 0.25), (0.1, 0.5)),
+                ((0.1, 0.5), (0.3, 0.75)),
+                ((0.7, 0.25), (0.9, 0.5)),
+                ((0.9, 0.5), (0.7, 0.75)),
+                ((0.58, 0.2), (0.42, 0.8)),
+            ]
+            .into_iter()
+            .map(|(a, b)| segment(p, a, b))
+            .fold(f32::INFINITY, f32::min),
+            Self::Info => ring(p, (0.5, 0.5), 0.4)
+                .min(segment(p, (0.5, 0.45), (0.5, 0.7)))
+                .min(segment(p, (0.5, 0.3), (0.5, 0.3))),
+        };
+        distance - Self::STROKE / 2.0
+    }
+}
+
+/// Rasterizes the requested [`Icon`] into an alpha mask.
+pub fn rasterize(request: RasterizeCustomGlyphRequest) -> Option<RasterizedCustomGlyph> {
+    let icon = Icon::from_id(request.id)?;
+    let (width, height) = (request.width as usize, request.height as usize);
+    let size = width.min(height) as f32;
+
+    let mut data = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let distance = icon.distance((x as f32 + 0.5) / size, (y as f32 + 0.5) / size);
+            let coverage = (0.5 - distance * size).clamp(0.0, 1.0);
+            data.push((coverage * 255.0).round() as u8);
+        }
+    }
+
+    Some(RasterizedCustomGlyph {
+        data,
+        content_type: ContentType::Mask,
+    })
+}
+
+fn segment(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (pa, ba) = ((p.0 - a.0, p.1 - a.1), (b.0 - a.0, b.1 - a.1));
+    let length = ba.0 * ba.0 + ba.1 * ba.1;
+    let t = if length > 0.0 {
+        ((pa.0 * ba.0 + pa.1 * ba.1) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (pa.0 - ba.0 * t).hypot(pa.1 - ba.1 * t)
+}
+
+fn ring(p: (f32, f32), center: (f32, f32), radius: f32) -> f32 {
+    ((p.0 - center.0).hypot(p.1 - center.1) - radius).abs()
+}
@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+
+use super::{Image, ImageQuad};
+
+/// Size of one `Image` instance in `image.wgsl`, two `vec4<f32>`s.
+const INSTANCE_SIZE: u64 = 2 * 16;
+/// Most texture memory kept for images that aren't on screen, in bytes. The
+/// least recently drawn ones are dropped first.
+const CACHE_BUDGET: usize = 16 * 1024 * 1024;
+
+struct CachedTexture {
+    bind_group: wgpu::BindGroup,
+    bytes: usize,
+    /// The last frame the image was drawn in.
+    last_used: u64,
+}
+
+/// Draws images as textured quads, keeping their textures around across
+/// frames keyed by the image content.
+pub struct ImagePipeline {
+    pipeline: wgpu::RenderPipeline,
+    viewport: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    texture_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    textures: HashMap<u64, CachedTexture>,
+    frame: u64,
+    instances: wgpu::Buffer,
+    /// Texture of each instance, in drawing order.
+    draws: Vec<u64>,
+}
+
+impl ImagePipeline {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("image.wgsl"));
+
+        let viewport = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("image viewport"),
+            size: 16,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let viewport_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("image viewport"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("image viewport"),
+            layout: &viewport_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: viewport.as_entire_binding(),
+            }],
+        });
+        let texture_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("image texture"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("image"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("image"),
+            bind_group_layouts: &[&viewport_layout, &texture_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("image"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: INSTANCE_SIZE,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            viewport,
+            bind_group,
+            texture_layout,
+            sampler,
+            textures: HashMap::new(),
+            frame: 0,
+            instances: instance_buffer(device, 4),
+            draws: Vec::new(),
+        }
+    }
+
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        resolution: [f32; 2],
+        images: &[ImageQuad],
+    ) {
+        self.frame += 1;
+        for quad in images {
+            let frame = self.frame;
+            let texture = self.textures.entry(quad.image.key()).or_insert_with(|| {
+                upload(
+                    device,
+                    queue,
+                    &self.texture_layout,
+                    &self.sampler,
+                    &quad.image,
+                )
+            });
+            texture.last_used = frame;
+        }
+        self.evict();
+
+        let instances: Vec<[[f32; 4]; 2]> = images
+            .iter()
+            .map(|quad| [quad.rect, [quad.corner_radius, quad.opacity, 0.0, 0.0]])
+            .collect();
+        let bytes: Vec<u8> = instances
+            .as_flattened()
+            .as_flattened()
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+
+        if bytes.len() as u64 > self.instances.size() {
+            self.instances = instance_buffer(device, images.len().next_power_of_two());
+        }
+        queue.write_buffer(&self.instances, 0, &bytes);
+
+        let viewport: Vec<u8> = [resolution[0], resolution[1], 0.0, 0.0]
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+        queue.write_buffer(&self.viewport, 0, &viewport);
+
+        self.draws = images.iter().map(|quad| quad.image.key()).collect();
+    }
+
+    pub fn render(&self, pass: &mut wgpu::RenderPass<'_>) {
+        if self.draws.is_empty() {
+            return;
+        }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.instances.slice(..));
+        for (index, key) in (0..).zip(&self.draws) {
+            pass.set_bind_group(1, &self.textures[key].bind_group, &[]);
+            pass.draw(0..4, index..index + 1);
+        }
+    }
+
+    /// Drops the least recently drawn textures until the ones that aren't
+    /// on screen fit in the budget.
+    fn evict(&mut self) {
+        let mut idle: Vec<(u64, u64, usize)> = self
+            .textures
+            .iter()
+            .filter(|(_, texture)| texture.last_used < self.frame)
+            .map(|(key, texture)| (texture.last_used, *key, texture.bytes))
+            .collect();
+        let mut bytes: usize = idle.iter().map(|(_, _, bytes)| bytes).sum();
+        idle.sort_unstable();
+        for (_, key, size) in idle {
+            if bytes <= CACHE_BUDGET {
+                break;
+            }
+            self.textures.remove(&key);
+            bytes -= size;
+        }
+    }
+}
+
+fn upload(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    image: &Image,
+) -> CachedTexture {
+    let size = wgpu::Extent3d {
+        width: image.width(),
+        height: image.height(),
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("image"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    queue.write_texture(
+        wgpu::TexelCopyTextureInfo {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        image.data(),
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * image.width()),
+            rows_per_image: Some(image.height()),
+        },
+        size,
+    );
+
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("image texture"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    });
+    CachedTexture {
+        bind_group,
+        bytes: image.data().len(),
+        last_used: 0,
+    }
+}
+
+fn instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("image instances"),
+        size: capacity as u64 * INSTANCE_SIZE,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
@@ -0,0 +1,55 @@
+struct Image {
+    // x, y, width, height in physical pixels
+    @location(0) rect: vec4<f32>,
+    // corner radius, opacity
+    @location(1) style: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) rect: vec4<f32>,
+    @location(2) @interpolate(flat) style: vec4<f32>,
+}
+
+// viewport width and height in physical pixels
+@group(0) @binding(0)
+var<uniform> viewport: vec4<f32>;
+
+@group(1) @binding(0)
+var image_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var image_sampler: sampler;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, image: Image) -> VertexOutput {
+    // a quad covering the image, drawn as a triangle strip
+    let corner = vec2<f32>(f32(index & 1u), f32((index >> 1u) & 1u));
+    let pixel = image.rect.xy + corner * image.rect.zw;
+    let ndc = pixel / viewport.xy * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0);
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(ndc, 0.0, 1.0);
+    out.uv = corner;
+    out.rect = image.rect;
+    out.style = image.style;
+    return out;
+}
+
+fn rounded_box(p: vec2<f32>, half_size: vec2<f32>, radius: f32) -> f32 {
+    let q = abs(p) - half_size + vec2<f32>(radius);
+    return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let half_size = in.rect.zw * 0.5;
+    let p = in.position.xy - in.rect.xy - half_size;
+    let radius = min(in.style.x, min(half_size.x, half_size.y));
+    let inside = clamp(0.5 - rounded_box(p, half_size, radius), 0.0, 1.0);
+
+    // the texture has straight alpha, the target premultiplied
+    let color = textureSample(image_texture, image_sampler, in.uv);
+    let alpha = color.a * inside * in.style.y;
+    return vec4<f32>(color.rgb * alpha, alpha);
+}
@@ -0,0 +1,224 @@
+use std::fmt;
+use std::fs::File;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Context;
+use winit::dpi::PhysicalSize;
+
+#[cfg(target_os = "macos")]
+pub use self::gpu::GpuRenderer;
+pub use self::icon::Icon;
+pub use self::software::{Pixmap, SoftwareRenderer};
+
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod atlas;
+#[cfg(target_os = "macos")]
+mod gpu;
+mod icon;
+#[cfg(target_os = "macos")]
+mod image;
+#[cfg(target_os = "macos")]
+mod panel;
+mod software;
+
+/// Everything that makes up one frame of the overlay.
+pub struct Scene<'a> {
+    pub background: glyphon::Color,
+    /// Drawn in order, before the text.
+    pub panels: Vec<Panel>,
+    /// Drawn over the panels, under the text.
+    pub images: Vec<ImageQuad>,
+    pub text_areas: Vec<glyphon::TextArea<'a>>,
+}
+
+/// A rounded rectangle with a border and a drop shadow.
+#[derive(Clone, Copy, Debug)]
+pub struct Panel {
+    /// Left, top, width and height in physical pixels.
+    pub rect: [f32; 4],
+    pub fill: glyphon::Color,
+    pub border: glyphon::Color,
+    pub shadow: glyphon::Color,
+    pub corner_radius: f32,
+    pub border_width: f32,
+    pub shadow_radius: f32,
+}
+
+impl Panel {
+    /// Signed distance from the pixel center at (`x`, `y`) to the panel edge,
+    /// negative inside. Mirrors `rounded_box` in `panel.wgsl`.
+    pub fn distance(&self, x: f32, y: f32) -> f32 {
+        rounded_box(self.rect, self.corner_radius, x, y)
+    }
+}
+
+/// An RGBA8 image with straight alpha, cheap to clone and identified by a
+/// hash of its content so renderers can cache its texture.
+#[derive(Clone)]
+pub struct Image {
+    key: u64,
+    width: u32,
+    height: u32,
+    data: Arc<[u8]>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32, data: Vec<u8>) -> Self {
+        assert_eq!(data.len(), width as usize * height as usize * 4);
+        let mut hasher = DefaultHasher::new();
+        (width, height, &data).hash(&mut hasher);
+        Self {
+            key: hasher.finish(),
+            width,
+            height,
+            data: data.into(),
+        }
+    }
+
+    /// A single pixel of `color`, stretched over whatever it's drawn into.
+    pub fn solid(color: glyphon::Color) -> Self {
+        Self::new(1, 1, color.as_rgba().to_vec())
+    }
+
+    /// Decodes the PNG at `path`, scaling it down to fit in `max_size` pixels
+    /// on each side.
+    pub fn load_png(path: &Path, max_size: u32) -> anyhow::Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        let mut decoder = png::Decoder::new(BufReader::new(file));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().context("failed to decode png")?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader
+            .next_frame(&mut buffer)
+            .context("failed to decode png")?;
+
+        let pixels = &buffer[..info.buffer_size()];
+        let data: Vec<u8> = match info.color_type {
+            png::ColorType::Rgba => pixels.to_vec(),
+            png::ColorType::Rgb => pixels
+                .chunks_exact(3)
+                .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 0xff])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => pixels
+                .chunks_exact(2)
+                .flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+                .collect(),
+            png::ColorType::Grayscale => pixels
+                .iter()
+                .flat_map(|&value| [value, value, value, 0xff])
+                .collect(),
+            png::ColorType::Indexed => anyhow::bail!("unexpected indexed png"),
+        };
+        Ok(Self::new(info.width, info.height, data).shrink(max_size))
+    }
+
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Color of the pixel nearest to (`u`, `v`) in the unit square.
+    pub fn sample(&self, u: f32, v: f32) -> glyphon::Color {
+        let x = ((u * self.width as f32) as u32).min(self.width - 1);
+        let y = ((v * self.height as f32) as u32).min(self.height - 1);
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        let [r, g, b, a] = self.data[i..i + 4].try_into().unwrap();
+        glyphon::Color::rgba(r, g, b, a)
+    }
+
+    /// Scales the image down, nearest neighbor, so neither side exceeds
+    /// `max_size`.
+    fn shrink(self, max_size: u32) -> Self {
+        let largest = self.width.max(self.height);
+        if largest <= max_size {
+            return self;
+        }
+        let scale = max_size as f32 / largest as f32;
+        let width = ((self.width as f32 * scale).round() as u32).max(1);
+        let height = ((self.height as f32 * scale).round() as u32).max(1);
+
+        let mut data = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in 0..height {
+            for x in 0..width {
+                let color = self.sample(
+                    (x as f32 + 0.5) / width as f32,
+                    (y as f32 + 0.5) / height as f32,
+                );
+                data.extend_from_slice(&color.as_rgba());
+            }
+        }
+        Self::new(width, height, data)
+    }
+}
+
+impl PartialEq for Image {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl fmt::Debug for Image {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Image")
+            .field("key", &self.key)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An image stretched over a rectangle with rounded corners.
+#[derive(Clone, Debug)]
+pub struct ImageQuad {
+    pub image: Image,
+    /// Left, top, width and height in physical pixels.
+    pub rect: [f32; 4],
+    pub corner_radius: f32,
+    pub opacity: f32,
+}
+
+impl ImageQuad {
+    /// Signed distance from (`x`, `y`) to the edge of the quad, negative
+    /// inside. Mirrors `rounded_box` in `image.wgsl`.
+    pub fn distance(&self, x: f32, y: f32) -> f32 {
+        rounded_box(self.rect, self.corner_radius, x, y)
+    }
+}
+
+fn rounded_box(rect: [f32; 4], corner_radius: f32, x: f32, y: f32) -> f32 {
+    let [left, top, width, height] = rect;
+    let (half_width, half_height) = (width / 2.0, height / 2.0);
+    let radius = corner_radius.min(half_width).min(half_height);
+
+    let qx = (x - left - half_width).abs() - half_width + radius;
+    let qy = (y - top - half_height).abs() - half_height + radius;
+    qx.max(0.0).hypot(qy.max(0.0)) + qx.max(qy).min(0.0) - radius
+}
+
+pub trait Renderer {
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    fn resize(&mut self, size: PhysicalSize<u32>) -> anyhow::Result<()>;
+
+    fn render(
+        &mut self,
+        font_system: &mut glyphon::FontSystem,
+        scene: Scene<'_>,
+    ) -> anyhow::Result<()>;
+}
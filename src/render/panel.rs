@@ -0,0 +1,169 @@
+use super::Panel;
+use super::gpu::{premultiplied, to_linear};
+
+/// Size of one `Panel` instance in `panel.wgsl`, five `vec4<f32>`s.
+const INSTANCE_SIZE: u64 = 5 * 16;
+
+/// Draws rounded panels, the chrome behind the text and the action buttons,
+/// as one instanced quad each.
+pub struct PanelPipeline {
+    pipeline: wgpu::RenderPipeline,
+    viewport: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    instances: wgpu::Buffer,
+    instance_count: u32,
+}
+
+impl PanelPipeline {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("panel.wgsl"));
+
+        let viewport = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("panel viewport"),
+            size: 16,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("panel"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("panel"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: viewport.as_entire_binding(),
+            }],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("panel"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("panel"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: INSTANCE_SIZE,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x4,
+                        1 => Float32x4,
+                        2 => Float32x4,
+                        3 => Float32x4,
+                        4 => Float32x4,
+                    ],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            viewport,
+            bind_group,
+            instances: instance_buffer(device, 4),
+            instance_count: 0,
+        }
+    }
+
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        resolution: [f32; 2],
+        panels: &[Panel],
+    ) {
+        let color = |color: glyphon::Color| {
+            let color = premultiplied(to_linear(color));
+            [color.r, color.g, color.b, color.a].map(|c| c as f32)
+        };
+        let instances: Vec<[[f32; 4]; 5]> = panels
+            .iter()
+            .map(|panel| {
+                [
+                    panel.rect,
+                    color(panel.fill),
+                    color(panel.border),
+                    color(panel.shadow),
+                    [
+                        panel.corner_radius,
+                        panel.border_width,
+                        panel.shadow_radius,
+                        0.0,
+                    ],
+                ]
+            })
+            .collect();
+        let bytes: Vec<u8> = instances
+            .as_flattened()
+            .as_flattened()
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+
+        if bytes.len() as u64 > self.instances.size() {
+            self.instances = instance_buffer(device, panels.len().next_power_of_two());
+        }
+        queue.write_buffer(&self.instances, 0, &bytes);
+
+        let viewport: Vec<u8> = [resolution[0], resolution[1], 0.0, 0.0]
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+        queue.write_buffer(&self.viewport, 0, &viewport);
+
+        self.instance_count = panels.len() as u32;
+    }
+
+    pub fn render(&self, pass: &mut wgpu::RenderPass<'_>) {
+        if self.instance_count == 0 {
+            return;
+        }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.instances.slice(..));
+        pass.draw(0..4, 0..self.instance_count);
+    }
+}
+
+fn instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("panel instances"),
+        size: capacity as u64 * INSTANCE_SIZE,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
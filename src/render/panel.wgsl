@@ -0,0 +1,66 @@
+struct Panel {
+    // x, y, width, height in physical pixels
+    @location(0) rect: vec4<f32>,
+    @location(1) fill: vec4<f32>,
+    @location(2) border: vec4<f32>,
+    @location(3) shadow: vec4<f32>,
+    // corner radius, border width, shadow radius
+    @location(4) shape: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) @interpolate(flat) rect: vec4<f32>,
+    @location(1) @interpolate(flat) fill: vec4<f32>,
+    @location(2) @interpolate(flat) border: vec4<f32>,
+    @location(3) @interpolate(flat) shadow: vec4<f32>,
+    @location(4) @interpolate(flat) shape: vec4<f32>,
+}
+
+// viewport width and height in physical pixels
+@group(0) @binding(0)
+var<uniform> viewport: vec4<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, panel: Panel) -> VertexOutput {
+    // a quad covering the panel and its shadow, drawn as a triangle strip
+    let corner = vec2<f32>(f32(index & 1u), f32((index >> 1u) & 1u));
+    let spread = vec2<f32>(panel.shape.z);
+    let pixel = panel.rect.xy - spread + corner * (panel.rect.zw + 2.0 * spread);
+    let ndc = pixel / viewport.xy * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0);
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(ndc, 0.0, 1.0);
+    out.rect = panel.rect;
+    out.fill = panel.fill;
+    out.border = panel.border;
+    out.shadow = panel.shadow;
+    out.shape = panel.shape;
+    return out;
+}
+
+fn rounded_box(p: vec2<f32>, half_size: vec2<f32>, radius: f32) -> f32 {
+    let q = abs(p) - half_size + vec2<f32>(radius);
+    return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let half_size = in.rect.zw * 0.5;
+    let p = in.position.xy - in.rect.xy - half_size;
+    let radius = min(in.shape.x, min(half_size.x, half_size.y));
+    let border_width = in.shape.y;
+    let shadow_radius = in.shape.z;
+
+    let distance = rounded_box(p, half_size, radius);
+    let inside = clamp(0.5 - distance, 0.0, 1.0);
+    let on_border = select(0.0, clamp(distance + border_width + 0.5, 0.0, 1.0), border_width > 0.0);
+
+    // colors are premultiplied, and the shadow is cut out below the panel so
+    // it doesn't darken a translucent fill
+    let panel = mix(in.fill, in.border, on_border) * inside;
+    let falloff = select(0.0, 1.0 - smoothstep(-shadow_radius, shadow_radius, distance), shadow_radius > 0.0);
+    let shadow = in.shadow * falloff * (1.0 - inside);
+
+    return panel + shadow * (1.0 - panel.a);
+}
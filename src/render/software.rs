@@ -0,0 +1,356 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Context;
+use glyphon::{
+    ContentType, FontSystem, RasterizeCustomGlyphRequest, SubpixelBin, SwashCache, SwashContent,
+};
+use winit::dpi::PhysicalSize;
+use winit::window::Window;
+
+use super::{ImageQuad, Panel, Renderer, Scene, icon};
+
+type Surface = softbuffer::Surface<Arc<dyn Window>, Arc<dyn Window>>;
+
+/// An RGBA8 image with straight alpha.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pixmap {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl Pixmap {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![0; width as usize * height as usize * 4],
+        }
+    }
+
+    pub fn fill(&mut self, color: glyphon::Color) {
+        for pixel in self.data.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&color.as_rgba());
+        }
+    }
+
+    /// Blends `color` over the pixel at (`x`, `y`), scaling its alpha by
+    /// `coverage`. Pixels outside of the pixmap are ignored.
+    pub fn blend(&mut self, x: i32, y: i32, color: glyphon::Color, coverage: u8) {
+        self.blend_channels(x, y, color, [coverage; 3]);
+    }
+
+    /// Like [`Pixmap::blend`], with a coverage for each of red, green and
+    /// blue, as in subpixel glyph masks.
+    pub fn blend_channels(&mut self, x: i32, y: i32, color: glyphon::Color, coverage: [u8; 3]) {
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height {
+            return;
+        }
+
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        let dst = &mut self.data[i..i + 4];
+
+        let src_a = coverage.map(|coverage| color.a() as f32 / 255.0 * coverage as f32 / 255.0);
+        let dst_a = dst[3] as f32 / 255.0;
+        // the pixel is as opaque as its most covered channel
+        let max_a = src_a.into_iter().fold(0.0, f32::max);
+        let out_a = max_a + dst_a * (1.0 - max_a);
+        if out_a <= 0.0 {
+            return;
+        }
+
+        let [r, g, b, _] = color.as_rgba();
+        for ((dst, src), src_a) in dst[..3].iter_mut().zip([r, g, b]).zip(src_a) {
+            let value = (src as f32 * src_a + *dst as f32 * dst_a * (1.0 - src_a)) / out_a;
+            *dst = value.round().min(255.0) as u8;
+        }
+        dst[3] = (out_a * 255.0).round() as u8;
+    }
+
+    /// Whether `other` is the same size and no channel of it differs by more
+    /// than `tolerance`.
+    #[cfg(test)]
+    pub fn matches(&self, other: &Self, tolerance: u8) -> bool {
+        (self.width, self.height) == (other.width, other.height)
+            && self
+                .data
+                .iter()
+                .zip(&other.data)
+                .all(|(a, b)| a.abs_diff(*b) <= tolerance)
+    }
+
+    /// Decodes the RGBA8 PNG at `path`, as written by [`Pixmap::write_png`].
+    #[cfg(test)]
+    pub fn read_png(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        let mut reader = png::Decoder::new(std::io::BufReader::new(file))
+            .read_info()
+            .context("failed to decode png")?;
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader
+            .next_frame(&mut data)
+            .context("failed to decode png")?;
+        anyhow::ensure!(
+            info.color_type == png::ColorType::Rgba && info.bit_depth == png::BitDepth::Eight,
+            "{} isn't an RGBA8 png",
+            path.display()
+        );
+        data.truncate(info.buffer_size());
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            data,
+        })
+    }
+
+    pub fn write_png(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let file =
+            File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()?
+            .write_image_data(&self.data)
+            .context("failed to encode png")?;
+        Ok(())
+    }
+}
+
+/// Rasterizes `scene` into `pixmap` on the CPU, laying out glyphs the same way
+/// glyphon does on the GPU.
+pub fn rasterize(
+    font_system: &mut FontSystem,
+    swash_cache: &mut SwashCache,
+    pixmap: &mut Pixmap,
+    scene: &Scene<'_>,
+) {
+    pixmap.fill(scene.background);
+    for panel in &scene.panels {
+        draw_panel(pixmap, panel);
+    }
+    for quad in &scene.images {
+        draw_image(pixmap, quad);
+    }
+
+    for area in &scene.text_areas {
+        let bounds = area.bounds;
+        let in_bounds = |x: i32, y: i32| {
+            x >= bounds.left && x < bounds.right && y >= bounds.top && y < bounds.bottom
+        };
+
+        for glyph in area.custom_glyphs {
+            let left = (area.left + glyph.left * area.scale).round() as i32;
+            let top = (area.top + glyph.top * area.scale).round() as i32;
+            let (width, height) = (
+                (glyph.width * area.scale).round() as u16,
+                (glyph.height * area.scale).round() as u16,
+            );
+            let Some(image) = icon::rasterize(RasterizeCustomGlyphRequest {
+                id: glyph.id,
+                width,
+                height,
+                x_bin: SubpixelBin::Zero,
+                y_bin: SubpixelBin::Zero,
+                scale: area.scale,
+            }) else {
+                continue;
+            };
+            let color = glyph.color.unwrap_or(area.default_color);
+
+            let stride = match image.content_type {
+                ContentType::Mask => 1,
+                ContentType::Color => 4,
+            };
+            for (i, pixel) in image.data.chunks_exact(stride).enumerate() {
+                let x = left + i as i32 % width as i32;
+                let y = top + i as i32 / width as i32;
+                if !in_bounds(x, y) {
+                    continue;
+                }
+                match image.content_type {
+                    ContentType::Mask => pixmap.blend(x, y, color, pixel[0]),
+                    ContentType::Color => {
+                        let [r, g, b, a] = pixel.try_into().unwrap();
+                        pixmap.blend(x, y, glyphon::Color::rgb(r, g, b), a)
+                    }
+                }
+            }
+        }
+
+        for run in area.buffer.layout_runs() {
+            let line_y = (run.line_y * area.scale).round() as i32;
+            for glyph in run.glyphs {
+                let physical = glyph.physical((area.left, area.top), area.scale);
+                let Some(image) = swash_cache.get_image(font_system, physical.cache_key) else {
+                    continue;
+                };
+                let color = glyph.color_opt.unwrap_or(area.default_color);
+
+                let left = physical.x + image.placement.left;
+                let top = line_y + physical.y - image.placement.top;
+                let width = image.placement.width as i32;
+                let stride = match image.content {
+                    SwashContent::Mask => 1,
+                    SwashContent::Color | SwashContent::SubpixelMask => 4,
+                };
+                for (i, pixel) in image.data.chunks_exact(stride).enumerate() {
+                    let x = left + i as i32 % width;
+                    let y = top + i as i32 / width;
+                    if x < bounds.left || x >= bounds.right || y < bounds.top || y >= bounds.bottom
+                    {
+                        continue;
+                    }
+
+                    match image.content {
+                        SwashContent::Mask => pixmap.blend(x, y, color, pixel[0]),
+                        SwashContent::Color => {
+                            let [r, g, b, a] = pixel.try_into().unwrap();
+                            pixmap.blend(x, y, glyphon::Color::rgb(r, g, b), a)
+                        }
+                        SwashContent::SubpixelMask => {
+                            pixmap.blend_channels(x, y, color, [pixel[0], pixel[1], pixel[2]])
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn draw_panel(pixmap: &mut Pixmap, panel: &Panel) {
+    let coverage = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+    for y in 0..pixmap.height as i32 {
+        for x in 0..pixmap.width as i32 {
+            let distance = panel.distance(x as f32 + 0.5, y as f32 + 0.5);
+            let inside = (0.5 - distance).clamp(0.0, 1.0);
+
+            if panel.shadow_radius > 0.0 {
+                let t = ((distance + panel.shadow_radius) / (2.0 * panel.shadow_radius))
+                    .clamp(0.0, 1.0);
+                let falloff = 1.0 - t * t * (3.0 - 2.0 * t);
+                pixmap.blend(x, y, panel.shadow, coverage(falloff * (1.0 - inside)));
+            }
+
+            if inside <= 0.0 {
+                continue;
+            }
+            let color = if panel.border_width > 0.0 && distance > -panel.border_width {
+                panel.border
+            } else {
+                panel.fill
+            };
+            pixmap.blend(x, y, color, coverage(inside));
+        }
+    }
+}
+
+fn draw_image(pixmap: &mut Pixmap, quad: &ImageQuad) {
+    let [left, top, width, height] = quad.rect;
+    let (x0, y0) = (left.floor().max(0.0) as i32, top.floor().max(0.0) as i32);
+    let x1 = ((left + width).ceil() as i32).min(pixmap.width as i32);
+    let y1 = ((top + height).ceil() as i32).min(pixmap.height as i32);
+
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            let inside = (0.5 - quad.distance(px, py)).clamp(0.0, 1.0) * quad.opacity;
+            if inside <= 0.0 {
+                continue;
+            }
+            let color = quad.image.sample((px - left) / width, (py - top) / height);
+            pixmap.blend(x, y, color, (inside * 255.0).round() as u8);
+        }
+    }
+}
+
+/// Renders on the CPU, presenting through softbuffer when attached to a
+/// window. Used when no wgpu adapter is available and for snapshots.
+pub struct SoftwareRenderer {
+    pixmap: Pixmap,
+    swash_cache: SwashCache,
+    surface: Option<Surface>,
+}
+
+impl SoftwareRenderer {
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    pub fn new(window: Arc<dyn Window>) -> anyhow::Result<Self> {
+        let size = window.surface_size();
+        let context = softbuffer::Context::new(window.clone())
+            .map_err(|err| anyhow::anyhow!("failed to create softbuffer context: {err}"))?;
+        let surface = Surface::new(&context, window)
+            .map_err(|err| anyhow::anyhow!("failed to create softbuffer surface: {err}"))?;
+
+        let mut renderer = Self {
+            surface: Some(surface),
+            ..Self::headless(size)
+        };
+        renderer.resize(size)?;
+        Ok(renderer)
+    }
+
+    pub fn headless(size: PhysicalSize<u32>) -> Self {
+        Self {
+            pixmap: Pixmap::new(size.width, size.height),
+            swash_cache: SwashCache::new(),
+            surface: None,
+        }
+    }
+
+    pub fn into_pixmap(self) -> Pixmap {
+        self.pixmap
+    }
+
+    fn present(&mut self) -> anyhow::Result<()> {
+        let Some(surface) = &mut self.surface else {
+            return Ok(());
+        };
+
+        surface.window().pre_present_notify();
+        let mut buffer = surface
+            .buffer_mut()
+            .map_err(|err| anyhow::anyhow!("failed to map softbuffer: {err}"))?;
+        // the window is transparent, so like the wgpu surface the buffer takes
+        // premultiplied alpha, in the top byte
+        for (dst, src) in buffer.iter_mut().zip(self.pixmap.data.chunks_exact(4)) {
+            let premultiply = |value: u8| ((value as u16 * src[3] as u16 + 127) / 255) as u8;
+            *dst = u32::from_be_bytes([
+                src[3],
+                premultiply(src[0]),
+                premultiply(src[1]),
+                premultiply(src[2]),
+            ]);
+        }
+        buffer
+            .present()
+            .map_err(|err| anyhow::anyhow!("failed to present softbuffer: {err}"))
+    }
+}
+
+impl Renderer for SoftwareRenderer {
+    fn resize(&mut self, size: PhysicalSize<u32>) -> anyhow::Result<()> {
+        self.pixmap = Pixmap::new(size.width, size.height);
+        if let Some(surface) = &mut self.surface
+            && let (Some(width), Some(height)) =
+                (NonZeroU32::new(size.width), NonZeroU32::new(size.height))
+        {
+            surface
+                .resize(width, height)
+                .map_err(|err| anyhow::anyhow!("failed to resize softbuffer surface: {err}"))?;
+        }
+        Ok(())
+    }
+
+    fn render(&mut self, font_system: &mut FontSystem, scene: Scene<'_>) -> anyhow::Result<()> {
+        rasterize(font_system, &mut self.swash_cache, &mut self.pixmap, &scene);
+        self.present()
+    }
+}
@@ -0,0 +1,482 @@
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::capture::{Capture, Context};
+use crate::normalization::Normalization;
+use crate::redaction::Redaction;
+
+/// The `[[rules]]` tables of the config file, tried in order for every
+/// capture. The first rule whose matchers all match applies; captures no
+/// rule matches are handled as if by a rule with nothing but defaults.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Rules(Vec<Rule>);
+
+impl Rules {
+    /// Index of the rule that applies to `capture`.
+    pub fn find(&self, capture: &Capture) -> Option<usize> {
+        self.0.iter().position(|rule| rule.matcher.matches(capture))
+    }
+
+    /// The rule that was found to apply to `capture`.
+    pub fn get(&self, capture: &Capture) -> Option<&Rule> {
+        self.0.get(capture.rule?)
+    }
+
+    /// Runs `capture` through the rule that applies to it, `normalization`
+    /// and `redaction`, returning `None` if it's dropped. `value` reads the
+    /// text of the element the selection was made in, if the rule needs it.
+    pub fn process(
+        &self,
+        mut capture: Capture,
+        value: impl FnOnce() -> Option<String>,
+        normalization: &Normalization,
+        redaction: &Redaction,
+    ) -> Option<Capture> {
+        capture.rule = self.find(&capture);
+        let rule = self.get(&capture);
+        if let Some(rule) = rule {
+            if rule.action == Action::Deny {
+                return None;
+            }
+            let value = rule
+                .needs_value()
+                .then(value)
+                .flatten()
+                .map(|value| value.encode_utf16().collect::<Vec<_>>());
+            rule.apply(&mut capture, value.as_deref());
+        }
+        rule.and_then(|rule| rule.normalization.as_ref())
+            .unwrap_or(normalization)
+            .apply(&mut capture);
+        redaction.apply(capture)
+    }
+
+    /// Whether `capture` goes to the sink called `sink`.
+    pub fn routes_to(&self, capture: &Capture, sink: &str) -> bool {
+        self.get(capture)
+            .and_then(|rule| rule.sinks.as_ref())
+            .is_none_or(|sinks| sinks.iter().any(|name| name == sink))
+    }
+
+    /// Whether `capture` is shown in the overlay.
+    pub fn shows(&self, capture: &Capture) -> bool {
+        self.get(capture).is_none_or(|rule| rule.overlay)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Rule {
+    #[serde(flatten)]
+    pub matcher: Matcher,
+    #[serde(default)]
+    pub action: Action,
+    /// Applied to the selection in order.
+    #[serde(default)]
+    pub transform: Vec<Transform>,
+    /// How the text is normalized, instead of the `normalization` table.
+    pub normalization: Option<Normalization>,
+    /// How many characters around the selection are captured with it.
+    pub context: Option<usize>,
+    /// Names of the sinks captures are delivered to, instead of all of them.
+    pub sinks: Option<Vec<String>>,
+    #[serde(default = "show_overlay")]
+    pub overlay: bool,
+    /// Whether the selection is copied to read it if the app doesn't expose
+    /// it, overriding `copy_fallback.enabled`.
+    pub copy_fallback: Option<bool>,
+}
+
+fn show_overlay() -> bool {
+    true
+}
+
+impl Rule {
+    /// Whether applying the rule needs the whole text of the element the
+    /// selection was made in.
+    pub fn needs_value(&self) -> bool {
+        self.context.is_some() || self.transform.contains(&Transform::WholeLine)
+    }
+
+    /// Transforms `capture` and adds the context around it. `value` is the
+    /// text of the element the selection was made in, in UTF-16 code units
+    /// like the capture's ranges.
+    pub fn apply(&self, capture: &mut Capture, value: Option<&[u16]>) {
+        // only a single selection within the value can be extended
+        let value = value
+            .filter(|value| matches!(&capture.ranges[..], [range] if range.end <= value.len()));
+        for transform in &self.transform {
+            match transform {
+                Transform::WholeLine => {
+                    if let Some(value) = value {
+                        let range = &mut capture.ranges[0];
+                        let is_break = |unit: &u16| *unit == u16::from(b'\n');
+                        range.start = value[..range.start]
+                            .iter()
+                            .rposition(is_break)
+                            .map_or(0, |index| index + 1);
+                        range.end = value[range.end..]
+                            .iter()
+                            .position(is_break)
+                            .map_or(value.len(), |index| range.end + index);
+                        capture.text = String::from_utf16_lossy(&value[range.clone()]);
+                    }
+                }
+                Transform::Trim => capture.text = capture.text.trim().to_owned(),
+            }
+        }
+        if let (Some(size), Some(value)) = (self.context, value) {
+            let range = &capture.ranges[0];
+            capture.context = Some(Context {
+                before: String::from_utf16_lossy(
+                    &value[range.start.saturating_sub(size)..range.start],
+                ),
+                after: String::from_utf16_lossy(
+                    &value[range.end..(range.end + size).min(value.len())],
+                ),
+            });
+        }
+    }
+}
+
+/// What a capture has to look like for a rule to apply. Unset fields match
+/// anything.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Matcher {
+    pub bundle_id: Option<String>,
+    /// Name of the app, ignoring case.
+    pub app: Option<String>,
+    pub window_title: Option<Pattern>,
+    /// Role of the selected element, like `AXTextArea`.
+    pub role: Option<String>,
+    pub text: Option<Pattern>,
+    /// Shortest and longest text, in characters.
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+}
+
+impl Matcher {
+    pub fn matches(&self, capture: &Capture) -> bool {
+        let length = capture.text.chars().count();
+        let is = |expected: &Option<String>, actual: &Option<String>| {
+            expected.is_none() || expected == actual
+        };
+        let is_match = |pattern: &Option<Pattern>, text: Option<&str>| {
+            pattern
+                .as_ref()
+                .is_none_or(|pattern| text.is_some_and(|text| pattern.0.is_match(text)))
+        };
+        is(&self.bundle_id, &capture.bundle_id)
+            && self.app.as_ref().is_none_or(|app| {
+                capture
+                    .app_name
+                    .as_ref()
+                    .is_some_and(|name| name.eq_ignore_ascii_case(app))
+            })
+            && is_match(&self.window_title, capture.window_title.as_deref())
+            && is(&self.role, &capture.role)
+            && is_match(&self.text, Some(&capture.text))
+            && self.min_length.is_none_or(|min| length >= min)
+            && self.max_length.is_none_or(|max| length <= max)
+    }
+}
+
+/// A regular expression, compiled when the config is loaded.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Pattern(Regex);
+
+impl TryFrom<String> for Pattern {
+    type Error = regex::Error;
+
+    fn try_from(pattern: String) -> Result<Self, Self::Error> {
+        Regex::new(&pattern).map(Self)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    #[default]
+    Allow,
+    /// Drops the capture, so it's neither shown, stored nor delivered.
+    Deny,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Transform {
+    /// Extends the selection to the start and end of the lines it's on.
+    WholeLine,
+    /// Removes whitespace around the selection.
+    Trim,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use super::*;
+    use crate::capture::Trigger;
+    use crate::redaction::Secret;
+
+    #[derive(Debug, Deserialize)]
+    struct Config {
+        rules: Rules,
+    }
+
+    /// Parses `[[rules]]` tables the way the config file does.
+    fn rules(toml: &str) -> Rules {
+        toml::from_str::<Config>(toml).unwrap().rules
+    }
+
+    fn rule(toml: &str) -> Rule {
+        rules(&format!("[[rules]]\n{toml}")).0.remove(0)
+    }
+
+    /// A capture of `text` made in Safari.
+    fn safari(text: &str) -> Capture {
+        Capture {
+            bundle_id: Some("com.apple.Safari".to_owned()),
+            app_name: Some("Safari".to_owned()),
+            window_title: Some("Pull request #42".to_owned()),
+            role: Some("AXWebArea".to_owned()),
+            ..Capture::new(text.to_owned(), Trigger::Poll)
+        }
+    }
+
+    /// A capture of `text` selected at `range` of an element's value.
+    fn selection(text: &str, range: Range<usize>) -> Capture {
+        Capture {
+            ranges: vec![range],
+            ..safari(text)
+        }
+    }
+
+    fn utf16(text: &str) -> Vec<u16> {
+        text.encode_utf16().collect()
+    }
+
+    #[test]
+    fn matches_anything_without_matchers() {
+        assert!(Matcher::default().matches(&safari("hello")));
+        assert!(Matcher::default().matches(&Capture::new(String::new(), Trigger::Poll)));
+    }
+
+    #[test]
+    fn matches_the_app() {
+        let matches = |toml: &str| rule(toml).matcher.matches(&safari("hello"));
+        assert!(matches(r#"bundle_id = "com.apple.Safari""#));
+        assert!(!matches(r#"bundle_id = "com.apple.safari""#));
+        assert!(matches(r#"app = "safari""#));
+        assert!(!matches(r#"app = "Safari Technology Preview""#));
+        assert!(matches(r#"role = "AXWebArea""#));
+        assert!(!matches(r#"role = "AXTextArea""#));
+        assert!(
+            !rule(r#"app = "Safari""#)
+                .matcher
+                .matches(&Capture::new("hello".to_owned(), Trigger::Poll))
+        );
+    }
+
+    #[test]
+    fn matches_patterns() {
+        let matches = |toml: &str| rule(toml).matcher.matches(&safari("fn main() {}"));
+        assert!(matches(r#"window_title = "^Pull request #\\d+$""#));
+        assert!(!matches(r#"window_title = "^Issue""#));
+        assert!(matches(r#"text = "^fn ""#));
+        assert!(!matches(r#"text = "^class ""#));
+
+        // a title pattern never matches a capture without a title
+        let untitled = Capture::new("hello".to_owned(), Trigger::Poll);
+        assert!(!rule(r#"window_title = ".*""#).matcher.matches(&untitled));
+    }
+
+    #[test]
+    fn matches_the_length_in_characters() {
+        let matcher = rule("min_length = 3\nmax_length = 5").matcher;
+        assert!(!matcher.matches(&safari("ab")));
+        assert!(matcher.matches(&safari("abc")));
+        assert!(matcher.matches(&safari("äöüßé")));
+        assert!(!matcher.matches(&safari("abcdef")));
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        assert!(toml::from_str::<Config>("[[rules]]\ntext = \"(\"").is_err());
+    }
+
+    #[test]
+    fn extends_the_selection_to_whole_lines() {
+        let value = utf16("first\nsecond line\nthird");
+        let mut capture = selection("cond", 8..12);
+        rule(r#"transform = ["whole-line"]"#).apply(&mut capture, Some(&value));
+        assert_eq!(capture.text, "second line");
+        assert_eq!(capture.ranges[0], 6..17);
+
+        // the first and last lines end at the ends of the value
+        let mut capture = selection("h", 20..21);
+        rule(r#"transform = ["whole-line"]"#).apply(&mut capture, Some(&value));
+        assert_eq!(capture.text, "third");
+        assert_eq!(capture.ranges[0], 18..23);
+    }
+
+    #[test]
+    fn transforms_in_order() {
+        let value = utf16("  indented  \nnext");
+        let mut capture = selection("de", 4..6);
+        rule(r#"transform = ["whole-line", "trim"]"#).apply(&mut capture, Some(&value));
+        assert_eq!(capture.text, "indented");
+    }
+
+    #[test]
+    fn leaves_multiple_selections_alone() {
+        let value = utf16("first\nsecond");
+        let mut capture = Capture {
+            ranges: vec![0..1, 6..7],
+            ..safari("fs")
+        };
+        rule("transform = [\"whole-line\"]\ncontext = 3").apply(&mut capture, Some(&value));
+        assert_eq!(capture.text, "fs");
+        assert_eq!(capture.context, None);
+    }
+
+    #[test]
+    fn adds_context_in_utf16_code_units() {
+        // the emoji takes two code units, which the ranges count
+        let value = utf16("a 😀 b selected c 😀 d");
+        let start = utf16("a 😀 b ").len();
+        let mut capture = selection("selected", start..start + 8);
+        rule("context = 5").apply(&mut capture, Some(&value));
+        assert_eq!(
+            capture.context,
+            Some(Context {
+                before: "😀 b ".to_owned(),
+                after: " c 😀".to_owned(),
+            })
+        );
+
+        // context stops at the ends of the value
+        let mut capture = selection("a", 0..1);
+        rule("context = 100").apply(&mut capture, Some(&value));
+        let context = capture.context.unwrap();
+        assert_eq!(context.before, "");
+        assert_eq!(context.after, " 😀 b selected c 😀 d");
+    }
+
+    #[test]
+    fn routes_to_the_sinks_of_the_rule() {
+        let rules = rules(
+            r#"
+            [[rules]]
+            app = "Safari"
+            sinks = ["webhook"]
+
+            [[rules]]
+            app = "Terminal"
+            overlay = false
+            "#,
+        );
+        let mut browser = safari("hello");
+        browser.rule = rules.find(&browser);
+        assert_eq!(browser.rule, Some(0));
+        assert!(rules.routes_to(&browser, "webhook"));
+        assert!(!rules.routes_to(&browser, "file"));
+        assert!(rules.shows(&browser));
+
+        let mut terminal = Capture {
+            app_name: Some("Terminal".to_owned()),
+            ..safari("hello")
+        };
+        terminal.rule = rules.find(&terminal);
+        assert!(rules.routes_to(&terminal, "file"));
+        assert!(!rules.shows(&terminal));
+
+        // captures no rule matches go everywhere
+        let mut other = Capture {
+            app_name: Some("Mail".to_owned()),
+            ..safari("hello")
+        };
+        other.rule = rules.find(&other);
+        assert_eq!(other.rule, None);
+        assert!(rules.routes_to(&other, "file"));
+        assert!(rules.shows(&other));
+    }
+
+    #[test]
+    fn processes_captures_through_rules_normalization_and_redaction() {
+        let rules = rules(
+            r#"
+            [[rules]]
+            app = "Terminal"
+            action = "deny"
+
+            [[rules]]
+            app = "Safari"
+            transform = ["whole-line"]
+            context = 3
+            normalization = { straighten_quotes = true }
+            "#,
+        );
+        let normalization = Normalization::default();
+        let redaction = Redaction::default();
+
+        let value = "intro\nsay “hi” to 4111 1111 1111 1111\nend";
+        let start = utf16("intro\nsay “").len();
+        let capture = rules
+            .process(
+                selection("hi", start..start + 2),
+                || Some(value.to_owned()),
+                &normalization,
+                &redaction,
+            )
+            .unwrap();
+        assert_eq!(capture.rule, Some(1));
+        assert!(
+            capture.text.starts_with("say \"hi\" to "),
+            "{}",
+            capture.text
+        );
+        // masked in the text and in the original it was normalized from
+        let original = capture.original.as_deref().unwrap();
+        assert!(original.starts_with("say “hi” to "), "{original}");
+        assert!(!capture.text.contains("4111") && !original.contains("4111"));
+        assert_eq!(capture.redactions, [Secret::CardNumber; 2]);
+        let context = capture.context.unwrap();
+        assert_eq!((&*context.before, &*context.after), ("ro\n", "\nen"));
+
+        let terminal = Capture {
+            app_name: Some("Terminal".to_owned()),
+            ..safari("hi")
+        };
+        let unread = || unreachable!("denied captures don't need the value");
+        assert_eq!(
+            rules.process(terminal, unread, &normalization, &redaction),
+            None
+        );
+    }
+
+    #[test]
+    fn processes_captures_no_rule_matches_with_the_defaults() {
+        let capture = Capture {
+            app_name: Some("Mail".to_owned()),
+            ..safari("zero\u{200B}width “quotes”")
+        };
+        let unread = || unreachable!("no rule needs the value");
+        let capture = Rules::default()
+            .process(
+                capture,
+                unread,
+                &Normalization::default(),
+                &Redaction::default(),
+            )
+            .unwrap();
+        assert_eq!(capture.rule, None);
+        assert_eq!(capture.text, "zerowidth “quotes”");
+        assert_eq!(
+            capture.original.as_deref(),
+            Some("zero\u{200B}width “quotes”")
+        );
+    }
+}
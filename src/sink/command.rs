@@ -0,0 +1,82 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use anyhow::Context;
+
+use super::CaptureSink;
+use crate::capture::Capture;
+
+/// Runs a command for every capture, with the capture as JSON on its
+/// standard input.
+pub struct CommandSink {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandSink {
+    pub fn new(program: &str, args: &[String]) -> Self {
+        Self {
+            program: program.to_owned(),
+            args: args.to_vec(),
+        }
+    }
+}
+
+impl CaptureSink for CommandSink {
+    fn send(&mut self, capture: &Capture) -> anyhow::Result<()> {
+        let json = serde_json::to_vec(capture).context("failed to serialize capture")?;
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .with_context(|| format!("failed to run {}", self.program))?;
+
+        // dropping stdin closes it, so the command sees the end of its input
+        let written = child.stdin.take().unwrap().write_all(&json);
+        let status = child.wait()?;
+        written.with_context(|| format!("failed to write to {}", self.program))?;
+        anyhow::ensure!(status.success(), "{} exited with {status}", self.program);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::capture::Trigger;
+
+    #[test]
+    fn pipes_captures_into_the_command() {
+        let path = std::env::temp_dir().join(format!(
+            "{}-command-{}.json",
+            env!("CARGO_PKG_NAME"),
+            std::process::id()
+        ));
+        let args = [
+            "-c".to_owned(),
+            "cat > \"$0\"".to_owned(),
+            path.display().to_string(),
+        ];
+        let capture = Capture::new("hello".to_owned(), Trigger::Poll);
+        CommandSink::new("sh", &args).send(&capture).unwrap();
+
+        let json = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(serde_json::from_str::<Capture>(&json).unwrap(), capture);
+    }
+
+    #[test]
+    fn fails_if_the_command_does() {
+        let capture = Capture::new("hello".to_owned(), Trigger::Poll);
+        let err = CommandSink::new("false", &[]).send(&capture).unwrap_err();
+        assert!(err.to_string().starts_with("false exited with"), "{err}");
+        assert!(
+            CommandSink::new("no-such-command", &[])
+                .send(&capture)
+                .is_err()
+        );
+    }
+}
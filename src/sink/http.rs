@@ -0,0 +1,182 @@
+use std::fmt::Write;
+use std::time::Duration;
+
+use anyhow::Context;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use super::CaptureSink;
+use crate::capture::Capture;
+
+/// Header carrying the signature of the body, as `sha256=<hex digest>`.
+const SIGNATURE_HEADER: &str = "X-Capture-Signature";
+
+/// POSTs captures as JSON to a webhook.
+pub struct HttpSink {
+    agent: ureq::Agent,
+    url: String,
+    /// Key the body is signed with, if any.
+    secret: Option<Vec<u8>>,
+}
+
+impl HttpSink {
+    /// A sink giving up on requests that take longer than `timeout`, so a
+    /// stalled server can't hold up the captures after it.
+    pub fn new(url: &str, secret: Option<&str>, timeout: Duration) -> Self {
+        let config = ureq::Agent::config_builder()
+            .timeout_global(Some(timeout))
+            .build();
+        Self {
+            agent: config.into(),
+            url: url.to_owned(),
+            secret: secret.map(|secret| secret.as_bytes().to_vec()),
+        }
+    }
+}
+
+impl CaptureSink for HttpSink {
+    fn send(&mut self, capture: &Capture) -> anyhow::Result<()> {
+        let body = serde_json::to_vec(capture).context("failed to serialize capture")?;
+        let mut request = self
+            .agent
+            .post(&self.url)
+            .header("Content-Type", "application/json");
+        if let Some(secret) = &self.secret {
+            request = request.header(SIGNATURE_HEADER, &format!("sha256={}", sign(secret, &body)));
+        }
+        request
+            .send(&body[..])
+            .with_context(|| format!("failed to post to {}", self.url))?;
+        Ok(())
+    }
+}
+
+/// Hex-encoded HMAC-SHA256 of `body` under `secret`.
+fn sign(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC takes keys of any length");
+    mac.update(body);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+    use crate::capture::Trigger;
+
+    /// The header lines and body of a request.
+    type Request = (Vec<String>, Vec<u8>);
+
+    /// Accepts one request on a local port, answering it with `status` after
+    /// `delay`, and returns the URL along with a handle yielding the headers
+    /// and body it got.
+    fn serve(status: &'static str, delay: Duration) -> (String, thread::JoinHandle<Request>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/captures", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut headers = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                headers.push(line.to_owned());
+            }
+            let length = headers
+                .iter()
+                .find_map(|header| {
+                    let (name, value) = header.split_once(": ")?;
+                    name.eq_ignore_ascii_case("content-length")
+                        .then(|| value.parse().unwrap())
+                })
+                .unwrap_or(0);
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+
+            thread::sleep(delay);
+            let response = format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\n\r\n");
+            // the client is gone if it timed out
+            let _ = stream.write_all(response.as_bytes());
+            (headers, body)
+        });
+        (url, server)
+    }
+
+    fn header<'a>(headers: &'a [String], name: &str) -> Option<&'a str> {
+        headers.iter().find_map(|header| {
+            let (key, value) = header.split_once(": ")?;
+            key.eq_ignore_ascii_case(name).then_some(value)
+        })
+    }
+
+    #[test]
+    fn posts_signed_captures() {
+        let (url, server) = serve("200 OK", Duration::ZERO);
+        let capture = Capture::new("hello".to_owned(), Trigger::Poll);
+        let mut sink = HttpSink::new(&url, Some("key"), Duration::from_secs(5));
+        sink.send(&capture).unwrap();
+
+        let (headers, body) = server.join().unwrap();
+        assert!(headers[0].starts_with("POST /captures "), "{headers:?}");
+        assert_eq!(header(&headers, "content-type"), Some("application/json"));
+        assert_eq!(serde_json::from_slice::<Capture>(&body).unwrap(), capture);
+        assert_eq!(
+            header(&headers, SIGNATURE_HEADER),
+            Some(format!("sha256={}", sign(b"key", &body)).as_str())
+        );
+    }
+
+    #[test]
+    fn leaves_unsigned_without_a_secret() {
+        let (url, server) = serve("200 OK", Duration::ZERO);
+        let mut sink = HttpSink::new(&url, None, Duration::from_secs(5));
+        sink.send(&Capture::new("hello".to_owned(), Trigger::Poll))
+            .unwrap();
+
+        let (headers, _) = server.join().unwrap();
+        assert_eq!(header(&headers, SIGNATURE_HEADER), None);
+    }
+
+    #[test]
+    fn fails_on_error_statuses() {
+        let (url, server) = serve("500 Internal Server Error", Duration::ZERO);
+        let mut sink = HttpSink::new(&url, None, Duration::from_secs(5));
+        assert!(
+            sink.send(&Capture::new("hello".to_owned(), Trigger::Poll))
+                .is_err()
+        );
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn gives_up_after_the_timeout() {
+        let (url, server) = serve("200 OK", Duration::from_millis(500));
+        let mut sink = HttpSink::new(&url, None, Duration::from_millis(100));
+        assert!(
+            sink.send(&Capture::new("hello".to_owned(), Trigger::Poll))
+                .is_err()
+        );
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn signs_with_hmac_sha256() {
+        assert_eq!(
+            sign(b"key", b"The quick brown fox jumps over the lazy dog"),
+            "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use std::path::PathBuf;
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use self::command::CommandSink;
+use self::http::HttpSink;
+use self::outbox::{Outbox, OutboxConfig};
+use self::socket::SocketSink;
+use self::stream::{JsonlSink, StdoutSink};
+use crate::capture::{Capture, Trigger};
+use crate::rules::Rules;
+
+mod command;
+mod http;
+pub mod outbox;
+mod socket;
+mod stream;
+
+/// Somewhere captures are delivered to, outside of the overlay.
+pub trait CaptureSink: Send {
+    fn send(&mut self, capture: &Capture) -> anyhow::Result<()>;
+}
+
+/// A sink as it's configured, in the `[[sinks]]` tables of the config file.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SinkConfig {
+    /// Names the sink in log messages and its outbox. Defaults to a
+    /// description of where it delivers to.
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub kind: SinkKind,
+    #[serde(default)]
+    pub filter: Filter,
+}
+
+impl SinkConfig {
+    pub fn name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| self.kind.describe())
+    }
+
+    /// The directory of the sink's outbox, named after the sink. No two sinks
+    /// may share one.
+    pub fn outbox_dir(&self) -> String {
+        self.name()
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+                _ => '_',
+            })
+            .collect()
+    }
+
+    /// Opens the outbox of captures waiting to be delivered to the sink.
+    pub fn outbox(&self, config: OutboxConfig) -> anyhow::Result<Outbox> {
+        let root = Outbox::root().context("no data directory")?;
+        Outbox::open(root.join(self.outbox_dir()), config)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SinkKind {
+    /// Appends one JSON object per line to a file.
+    Jsonl { path: PathBuf },
+    /// Prints one JSON object per line.
+    Stdout,
+    /// Sends one JSON object per line to every client connected to a Unix
+    /// domain socket.
+    Socket { path: PathBuf },
+    /// POSTs each capture as JSON, signed with HMAC-SHA256 if there's a
+    /// secret.
+    Http {
+        url: String,
+        secret: Option<String>,
+        /// How long a request may take in all, in milliseconds.
+        #[serde(default = "http_timeout_ms")]
+        timeout_ms: u64,
+    },
+    /// Runs a command with the capture as JSON on its standard input.
+    Command {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+fn http_timeout_ms() -> u64 {
+    10_000
+}
+
+impl SinkKind {
+    pub fn open(&self) -> anyhow::Result<Box<dyn CaptureSink>> {
+        Ok(match self {
+            Self::Jsonl { path } => Box::new(JsonlSink::open(path)?),
+            Self::Stdout => Box::new(StdoutSink),
+            Self::Socket { path } => Box::new(SocketSink::bind(path)?),
+            Self::Http {
+                url,
+                secret,
+                timeout_ms,
+            } => Box::new(HttpSink::new(
+                url,
+                secret.as_deref(),
+                Duration::from_millis(*timeout_ms),
+            )),
+            Self::Command { program, args } => Box::new(CommandSink::new(program, args)),
+        })
+    }
+
+    /// A short description for log messages.
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Jsonl { path } => format!("jsonl {}", path.display()),
+            Self::Stdout => "stdout".to_owned(),
+            Self::Socket { path } => format!("socket {}", path.display()),
+            Self::Http { url, .. } => format!("http {url}"),
+            Self::Command { program, .. } => format!("command {program}"),
+        }
+    }
+}
+
+/// Which captures a sink receives. Empty lists don't filter anything.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Filter {
+    /// Bundle identifiers or names of the apps to take captures from.
+    pub apps: Vec<String>,
+    /// Bundle identifiers or names of the apps to ignore.
+    pub exclude_apps: Vec<String>,
+    pub triggers: Vec<Trigger>,
+    /// Shortest text to deliver, in characters.
+    pub min_length: usize,
+}
+
+impl Filter {
+    pub fn matches(&self, capture: &Capture) -> bool {
+        let is_app = |app: &String| {
+            capture.bundle_id.as_ref() == Some(app)
+                || capture
+                    .app_name
+                    .as_ref()
+                    .is_some_and(|name| name.eq_ignore_ascii_case(app))
+        };
+        (self.apps.is_empty() || self.apps.iter().any(is_app))
+            && !self.exclude_apps.iter().any(is_app)
+            && (self.triggers.is_empty() || self.triggers.contains(&capture.trigger))
+            && capture.text.chars().count() >= self.min_length
+    }
+}
+
+/// Opens the configured sinks and queues captures sent to the returned
+/// channel in each one's outbox, from which they're delivered on a thread per
+/// sink, so a slow or unreachable sink doesn't hold up the overlay or the
+/// other sinks. Captures go to the sinks their rule routes them to, if it
+/// does. Returns `None` if there are no sinks.
+pub fn spawn(
+    configs: &[SinkConfig],
+    outbox: OutboxConfig,
+    rules: Rules,
+) -> Option<mpsc::Sender<Capture>> {
+    let sinks: Vec<(String, Filter, Arc<Outbox>, mpsc::Sender<()>)> = configs
+        .iter()
+        .filter_map(|config| {
+            let name = config.name();
+            let opened = config
+                .kind
+                .open()
+                .and_then(|sink| Ok((sink, Arc::new(config.outbox(outbox)?))));
+            match opened {
+                Ok((sink, outbox)) => {
+                    // delivers whatever was left queued by a previous run
+                    let notify_tx = outbox.clone().deliver(name.clone(), sink);
+                    Some((name, config.filter.clone(), outbox, notify_tx))
+                }
+                Err(err) => {
+                    eprintln!("failed to open sink {name}: {err:#}");
+                    None
+                }
+            }
+        })
+        .collect();
+    if sinks.is_empty() {
+        return None;
+    }
+
+    let (capture_tx, capture_rx) = mpsc::channel::<Capture>();
+    thread::spawn(move || {
+        for capture in capture_rx {
+            for (name, filter, outbox, notify_tx) in &sinks {
+                if !filter.matches(&capture) || !rules.routes_to(&capture, name) {
+                    continue;
+                }
+                match outbox.push(&capture) {
+                    Ok(()) => {
+                        let _ = notify_tx.send(());
+                    }
+                    Err(err) => eprintln!("failed to queue capture for {name}: {err:#}"),
+                }
+            }
+        }
+    });
+    Some(capture_tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from(bundle_id: &str, app_name: &str, trigger: Trigger) -> Capture {
+        Capture {
+            bundle_id: Some(bundle_id.to_owned()),
+            app_name: Some(app_name.to_owned()),
+            ..Capture::new("hello".to_owned(), trigger)
+        }
+    }
+
+    #[test]
+    fn matches_everything_by_default() {
+        assert!(Filter::default().matches(&Capture::new(String::new(), Trigger::Poll)));
+    }
+
+    #[test]
+    fn matches_apps_by_bundle_id_or_name() {
+        let safari = from("com.apple.Safari", "Safari", Trigger::Poll);
+        let mail = from("com.apple.mail", "Mail", Trigger::Poll);
+        let filter = Filter {
+            apps: vec!["com.apple.Safari".to_owned(), "mail".to_owned()],
+            ..Filter::default()
+        };
+        assert!(filter.matches(&safari));
+        assert!(filter.matches(&mail));
+        assert!(!filter.matches(&from("com.apple.Terminal", "Terminal", Trigger::Poll)));
+
+        let filter = Filter {
+            exclude_apps: vec!["safari".to_owned()],
+            ..Filter::default()
+        };
+        assert!(!filter.matches(&safari));
+        assert!(filter.matches(&mail));
+    }
+
+    #[test]
+    fn matches_triggers_and_length() {
+        let filter = Filter {
+            triggers: vec![Trigger::Copy],
+            min_length: 5,
+            ..Filter::default()
+        };
+        assert!(filter.matches(&from("com.apple.Safari", "Safari", Trigger::Copy)));
+        assert!(!filter.matches(&from("com.apple.Safari", "Safari", Trigger::Poll)));
+        let short = Capture::new("héll".to_owned(), Trigger::Copy);
+        assert!(!filter.matches(&short));
+    }
+}
@@ -0,0 +1,347 @@
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use super::CaptureSink;
+use crate::capture::Capture;
+
+const DEAD_LETTER_FILE: &str = "dead-letter.jsonl";
+/// Where the dead-letter file is moved once it takes up its share of the
+/// outbox, replacing the dead letters moved there before.
+const OLD_DEAD_LETTER_FILE: &str = "dead-letter.1.jsonl";
+
+/// How queued captures are retried and how much of them is kept.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default)]
+pub struct OutboxConfig {
+    /// Failed deliveries of a capture before it's moved to the dead-letter
+    /// file.
+    pub max_attempts: u32,
+    /// Longest wait between retries, which otherwise double from a second.
+    pub max_backoff_secs: u64,
+    /// Most disk space queued and dead-lettered captures take per sink. The
+    /// oldest queued captures are dropped beyond it, and dead letters are
+    /// rotated once they take up a quarter of it.
+    pub max_bytes: u64,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 10,
+            max_backoff_secs: 300,
+            max_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl OutboxConfig {
+    /// How long to wait after the `failures`th failed delivery in a row.
+    fn backoff(&self, failures: u32) -> Duration {
+        let secs = 1u64
+            .checked_shl(failures.saturating_sub(1))
+            .unwrap_or(u64::MAX);
+        Duration::from_secs(secs.min(self.max_backoff_secs))
+    }
+}
+
+/// How far behind a sink is.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Status {
+    pub pending: usize,
+    /// Disk space taken by queued captures and dead letters.
+    pub bytes: u64,
+    /// When the oldest undelivered capture was queued.
+    pub oldest: Option<SystemTime>,
+    pub dead_letters: usize,
+}
+
+/// A directory of captures waiting to be delivered to a sink, one file each,
+/// named so they sort in the order they were queued. A capture's file is only
+/// removed once the sink took it, so delivery is at least once.
+pub struct Outbox {
+    dir: PathBuf,
+    config: OutboxConfig,
+    counter: AtomicU64,
+}
+
+impl Outbox {
+    pub fn root() -> Option<PathBuf> {
+        Some(
+            dirs::data_dir()?
+                .join(env!("CARGO_PKG_NAME"))
+                .join("outbox"),
+        )
+    }
+
+    pub fn open(dir: PathBuf, config: OutboxConfig) -> anyhow::Result<Self> {
+        fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        Ok(Self {
+            dir,
+            config,
+            counter: AtomicU64::new(0),
+        })
+    }
+
+    /// Queues `capture`, making room by dropping the oldest captures if the
+    /// outbox is full.
+    pub fn push(&self, capture: &Capture) -> anyhow::Result<()> {
+        let json = serde_json::to_vec(capture).context("failed to serialize capture")?;
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let counter = self.counter.fetch_add(1, Ordering::Relaxed) % 1_000_000;
+        let name = format!("{millis:016}-{counter:06}.json");
+
+        // written under another name first, so a crash never leaves half a
+        // capture in the queue
+        let temporary = self.dir.join(format!("{name}.tmp"));
+        fs::write(&temporary, json)
+            .with_context(|| format!("failed to write {}", temporary.display()))?;
+        fs::rename(&temporary, self.dir.join(&name))
+            .with_context(|| format!("failed to queue {name}"))?;
+
+        let entries = self.entries()?;
+        let mut bytes: u64 =
+            entries.iter().map(|(_, size)| size).sum::<u64>() + self.dead_letter_bytes()?;
+        for (path, size) in entries {
+            if bytes <= self.config.max_bytes {
+                break;
+            }
+            eprintln!(
+                "outbox {} is full, dropping {}",
+                self.dir.display(),
+                path.display()
+            );
+            remove(&path)?;
+            bytes -= size;
+        }
+        Ok(())
+    }
+
+    pub fn status(&self) -> anyhow::Result<Status> {
+        let entries = self.entries()?;
+        let mut dead_letters = 0;
+        for name in [OLD_DEAD_LETTER_FILE, DEAD_LETTER_FILE] {
+            dead_letters += match fs::File::open(self.dir.join(name)) {
+                Ok(file) => BufReader::new(file).lines().count(),
+                Err(err) if err.kind() == ErrorKind::NotFound => 0,
+                Err(err) => return Err(err).context("failed to read dead letters"),
+            };
+        }
+        Ok(Status {
+            pending: entries.len(),
+            bytes: entries.iter().map(|(_, size)| size).sum::<u64>() + self.dead_letter_bytes()?,
+            oldest: entries.first().and_then(|(path, _)| queued_at(path)),
+            dead_letters,
+        })
+    }
+
+    /// Delivers queued captures to `sink` on a thread of its own, oldest
+    /// first, until the returned sender is dropped. Send to it to have newly
+    /// queued captures picked up.
+    pub fn deliver(
+        self: Arc<Self>,
+        name: String,
+        mut sink: Box<dyn CaptureSink>,
+    ) -> mpsc::Sender<()> {
+        let (notify_tx, notify_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut failures = 0;
+            loop {
+                let oldest = match self.entries() {
+                    Ok(entries) => entries.into_iter().next().map(|(path, _)| path),
+                    Err(err) => {
+                        eprintln!("failed to read outbox of {name}: {err:#}");
+                        None
+                    }
+                };
+                let Some(path) = oldest else {
+                    if notify_rx.recv().is_err() {
+                        return;
+                    }
+                    continue;
+                };
+
+                let capture = match read(&path) {
+                    Ok(capture) => capture,
+                    Err(err) => {
+                        self.dead_letter(&path, &err);
+                        continue;
+                    }
+                };
+                match sink.send(&capture) {
+                    Ok(()) => {
+                        failures = 0;
+                        if let Err(err) = remove(&path) {
+                            eprintln!("failed to dequeue capture for {name}: {err:#}");
+                        }
+                    }
+                    Err(err) => {
+                        failures += 1;
+                        eprintln!(
+                            "failed to deliver capture to {name} (attempt {failures}): {err:#}"
+                        );
+                        if failures >= self.config.max_attempts {
+                            self.dead_letter(&path, &err);
+                            failures = 0;
+                        } else {
+                            thread::sleep(self.config.backoff(failures));
+                        }
+                    }
+                }
+            }
+        });
+        notify_tx
+    }
+
+    /// Moves the capture at `path` to the dead-letter file, with the error
+    /// that kept it from being delivered, rotating the file if it's grown
+    /// past its share of the outbox.
+    fn dead_letter(&self, path: &Path, err: &anyhow::Error) {
+        let result = (|| -> anyhow::Result<()> {
+            let capture = fs::read_to_string(path)?;
+            let line = serde_json::json!({
+                "error": format!("{err:#}"),
+                "capture": serde_json::from_str::<serde_json::Value>(&capture)
+                    .unwrap_or(serde_json::Value::String(capture)),
+            });
+            let dead_letters = self.dir.join(DEAD_LETTER_FILE);
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&dead_letters)?;
+            writeln!(file, "{line}")?;
+            if file.metadata()?.len() > self.config.max_bytes / 4 {
+                fs::rename(&dead_letters, self.dir.join(OLD_DEAD_LETTER_FILE))?;
+            }
+            remove(path)
+        })();
+        if let Err(err) = result {
+            eprintln!("failed to dead-letter {}: {err:#}", path.display());
+        }
+    }
+
+    /// Disk space taken by dead letters.
+    fn dead_letter_bytes(&self) -> anyhow::Result<u64> {
+        let mut bytes = 0;
+        for name in [OLD_DEAD_LETTER_FILE, DEAD_LETTER_FILE] {
+            bytes += match fs::metadata(self.dir.join(name)) {
+                Ok(metadata) => metadata.len(),
+                Err(err) if err.kind() == ErrorKind::NotFound => 0,
+                Err(err) => return Err(err).context("failed to read dead letters"),
+            };
+        }
+        Ok(bytes)
+    }
+
+    /// Queued captures and their sizes, oldest first.
+    fn entries(&self) -> anyhow::Result<Vec<(PathBuf, u64)>> {
+        let mut entries = Vec::new();
+        let dir = fs::read_dir(&self.dir)
+            .with_context(|| format!("failed to read {}", self.dir.display()))?;
+        for entry in dir {
+            let entry = entry?;
+            let path = entry.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                entries.push((path, entry.metadata()?.len()));
+            }
+        }
+        entries.sort();
+        Ok(entries)
+    }
+}
+
+fn read(path: &Path) -> anyhow::Result<Capture> {
+    let json =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_str(&json).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+/// Removes a queued capture, which may already be gone if it was dropped
+/// to make room while being delivered.
+fn remove(path: &Path) -> anyhow::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => {
+            Err(err).with_context(|| format!("failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// When the capture at `path` was queued, from its name.
+fn queued_at(path: &Path) -> Option<SystemTime> {
+    let name = path.file_stem()?.to_str()?;
+    let millis = name.split('-').next()?.parse().ok()?;
+    Some(UNIX_EPOCH + Duration::from_millis(millis))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Trigger;
+
+    fn outbox(name: &str, max_bytes: u64) -> Outbox {
+        let dir = std::env::temp_dir().join(format!(
+            "{}-outbox-{name}-{}",
+            env!("CARGO_PKG_NAME"),
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let config = OutboxConfig {
+            max_bytes,
+            ..OutboxConfig::default()
+        };
+        Outbox::open(dir, config).unwrap()
+    }
+
+    fn capture(i: usize) -> Capture {
+        Capture::new(format!("{i} {}", "x".repeat(200)), Trigger::Poll)
+    }
+
+    #[test]
+    fn drops_the_oldest_captures_when_full() {
+        let outbox = outbox("full", 2000);
+        for i in 0..20 {
+            outbox.push(&capture(i)).unwrap();
+        }
+
+        let status = outbox.status().unwrap();
+        assert!(status.bytes <= 2000, "{status:?}");
+        let (oldest, _) = outbox.entries().unwrap().remove(0);
+        assert!(
+            read(&oldest)
+                .unwrap()
+                .text
+                .starts_with(&format!("{} ", 20 - status.pending))
+        );
+    }
+
+    #[test]
+    fn keeps_dead_letters_within_the_limit() {
+        let outbox = outbox("dead-letters", 4000);
+        let err = anyhow::anyhow!("refused");
+        for i in 0..100 {
+            outbox.push(&capture(i)).unwrap();
+            let (path, _) = outbox.entries().unwrap().remove(0);
+            outbox.dead_letter(&path, &err);
+        }
+
+        let status = outbox.status().unwrap();
+        assert_eq!(status.pending, 0);
+        assert!(status.dead_letters > 0);
+        // a quarter in each dead-letter file, and at most a line over
+        assert!(status.bytes <= 4000 / 2 + 1000, "{status:?}");
+    }
+}
@@ -0,0 +1,133 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Context;
+
+use super::CaptureSink;
+use super::stream::write_line;
+use crate::capture::Capture;
+
+/// How long a client may take to read a capture before it's dropped.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Broadcasts captures to every client connected to a Unix domain socket,
+/// one JSON object per line.
+pub struct SocketSink {
+    path: PathBuf,
+    listener: UnixListener,
+    clients: Vec<UnixStream>,
+}
+
+impl SocketSink {
+    pub fn bind(path: &Path) -> anyhow::Result<Self> {
+        // a socket left behind by a previous run would make binding fail
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => {
+                return Err(err).with_context(|| format!("failed to remove {}", path.display()));
+            }
+            _ => {}
+        }
+        let listener = UnixListener::bind(path)
+            .with_context(|| format!("failed to bind {}", path.display()))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            path: path.to_owned(),
+            listener,
+            clients: Vec::new(),
+        })
+    }
+
+    /// Takes on the clients that connected since the last capture.
+    fn accept(&mut self) -> anyhow::Result<()> {
+        loop {
+            match self.listener.accept() {
+                Ok((client, _)) => {
+                    client.set_nonblocking(false)?;
+                    client.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                    self.clients.push(client);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(err) => return Err(err).context("failed to accept client"),
+            }
+        }
+    }
+}
+
+impl CaptureSink for SocketSink {
+    fn send(&mut self, capture: &Capture) -> anyhow::Result<()> {
+        self.accept()?;
+        // clients that hung up or can't keep up are dropped, and like any
+        // broadcast, captures made while no one is listening are gone
+        self.clients
+            .retain_mut(|client| write_line(client, capture).is_ok());
+        Ok(())
+    }
+}
+
+impl Drop for SocketSink {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+
+    use super::*;
+    use crate::capture::Trigger;
+
+    fn socket_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "{}-{name}-{}.sock",
+            env!("CARGO_PKG_NAME"),
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn broadcasts_captures_to_connected_clients() {
+        let path = socket_path("broadcast");
+        let mut sink = SocketSink::bind(&path).unwrap();
+        // captures nobody hears are gone, without failing
+        sink.send(&Capture::new("unheard".to_owned(), Trigger::Poll))
+            .unwrap();
+
+        let clients = [
+            UnixStream::connect(&path).unwrap(),
+            UnixStream::connect(&path).unwrap(),
+        ];
+        let capture = Capture::new("hello".to_owned(), Trigger::Poll);
+        sink.send(&capture).unwrap();
+        for client in clients {
+            let mut line = String::new();
+            BufReader::new(client).read_line(&mut line).unwrap();
+            assert_eq!(serde_json::from_str::<Capture>(&line).unwrap(), capture);
+        }
+    }
+
+    #[test]
+    fn drops_clients_that_hung_up() {
+        let path = socket_path("hung-up");
+        let mut sink = SocketSink::bind(&path).unwrap();
+        drop(UnixStream::connect(&path).unwrap());
+        let capture = Capture::new("hello".to_owned(), Trigger::Poll);
+        sink.send(&capture).unwrap();
+        sink.send(&capture).unwrap();
+        assert!(sink.clients.is_empty());
+    }
+
+    #[test]
+    fn removes_the_socket_when_dropped() {
+        let path = socket_path("removed");
+        // one left behind by a previous run is replaced
+        drop(UnixListener::bind(&path));
+        let sink = SocketSink::bind(&path).unwrap();
+        assert!(path.exists());
+        drop(sink);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,86 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use anyhow::Context;
+
+use super::CaptureSink;
+use crate::capture::Capture;
+
+/// Appends captures to a file, one JSON object per line.
+pub struct JsonlSink {
+    file: File,
+}
+
+impl JsonlSink {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        Ok(Self { file })
+    }
+}
+
+impl CaptureSink for JsonlSink {
+    fn send(&mut self, capture: &Capture) -> anyhow::Result<()> {
+        write_line(&mut self.file, capture)
+    }
+}
+
+/// Prints captures, one JSON object per line.
+pub struct StdoutSink;
+
+impl CaptureSink for StdoutSink {
+    fn send(&mut self, capture: &Capture) -> anyhow::Result<()> {
+        write_line(&mut io::stdout().lock(), capture)
+    }
+}
+
+/// Writes `capture` as a line of JSON, in a single write so concurrent
+/// writers don't interleave.
+pub(super) fn write_line(writer: &mut impl Write, capture: &Capture) -> anyhow::Result<()> {
+    let mut line = serde_json::to_vec(capture).context("failed to serialize capture")?;
+    line.push(b'\n');
+    writer.write_all(&line)?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::Trigger;
+
+    #[test]
+    fn appends_a_line_per_capture() {
+        let dir = std::env::temp_dir().join(format!(
+            "{}-jsonl-{}",
+            env!("CARGO_PKG_NAME"),
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("captures.jsonl");
+        let captures = [
+            Capture::new("hello".to_owned(), Trigger::Poll),
+            Capture::new("multiple\nlines".to_owned(), Trigger::Copy),
+        ];
+        for capture in &captures {
+            // reopening appends to what's there
+            JsonlSink::open(&path).unwrap().send(capture).unwrap();
+        }
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        let lines: Vec<Capture> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines, captures);
+    }
+}
@@ -0,0 +1,271 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::capture::Capture;
+use crate::event::Event;
+use crate::lifecycle::{Clock, SystemClock};
+
+/// How long a selection has to stay put before it's captured.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default)]
+pub struct Stabilization {
+    pub quiet_ms: u64,
+}
+
+impl Default for Stabilization {
+    fn default() -> Self {
+        Self { quiet_ms: 300 }
+    }
+}
+
+impl Stabilization {
+    fn quiet(&self) -> Duration {
+        Duration::from_millis(self.quiet_ms)
+    }
+}
+
+/// Turns the selections the controller observes into events, holding each
+/// back until it's settled: unchanged for the quiet period with the mouse
+/// button up. Repeats of the last capture are dropped, and a selection that
+/// extends it replaces it instead of being captured anew.
+pub struct Stabilizer<C = SystemClock> {
+    clock: C,
+    config: Stabilization,
+    /// The selection waiting to settle, and when it last changed.
+    pending: Option<(Capture, Instant)>,
+    /// The last selection reported.
+    emitted: Option<Capture>,
+}
+
+impl<C: Clock> Stabilizer<C> {
+    pub fn new(clock: C, config: Stabilization) -> Self {
+        Self {
+            clock,
+            config,
+            pending: None,
+            emitted: None,
+        }
+    }
+
+    /// Takes in the current selection, returning an event once it has
+    /// settled.
+    pub fn observe(&mut self, selection: Option<Capture>, button_down: bool) -> Option<Event> {
+        let now = self.clock.now();
+        let Some(mut capture) = selection else {
+            self.pending = None;
+            return self.emitted.take().map(|_| Event::SelectionCleared);
+        };
+
+        match &self.pending {
+            Some((pending, _)) if is_same(pending, &capture) => {}
+            Some((pending, _)) if is_extension(pending, &capture) => {
+                // still being dragged out, so it began when the first part did
+                capture.timestamp = pending.timestamp;
+                self.pending = Some((capture, now));
+            }
+            _ if self
+                .emitted
+                .as_ref()
+                .is_some_and(|emitted| is_same(emitted, &capture)) =>
+            {
+                self.pending = None;
+            }
+            _ => self.pending = Some((capture, now)),
+        }
+
+        let (_, since) = self.pending.as_ref()?;
+        if button_down || now.duration_since(*since) < self.config.quiet() {
+            return None;
+        }
+        let (mut capture, _) = self.pending.take()?;
+        let event = match &self.emitted {
+            Some(emitted) if is_extension(emitted, &capture) => {
+                capture.timestamp = emitted.timestamp;
+                capture.extends_previous = true;
+                Event::SelectionExtended(capture.clone())
+            }
+            _ => Event::SelectionChanged(capture.clone()),
+        };
+        self.emitted = Some(capture);
+        Some(event)
+    }
+
+    /// When the pending selection settles, if the mouse button is up by then.
+    pub fn deadline(&self) -> Option<Instant> {
+        let (_, since) = self.pending.as_ref()?;
+        Some(*since + self.config.quiet())
+    }
+}
+
+fn is_same(a: &Capture, b: &Capture) -> bool {
+    a.pid == b.pid && a.text == b.text && a.ranges == b.ranges
+}
+
+/// Whether `b` is `a` with more selected on either end.
+fn is_extension(a: &Capture, b: &Capture) -> bool {
+    let ranges_contain = match (a.ranges.as_slice(), b.ranges.as_slice()) {
+        ([a], [b]) => b.start <= a.start && a.end <= b.end,
+        _ => true,
+    };
+    a.pid == b.pid
+        && b.text.len() > a.text.len()
+        && (b.text.starts_with(&a.text) || b.text.ends_with(&a.text))
+        && ranges_contain
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+    use crate::capture::Trigger;
+    use crate::lifecycle::ManualClock;
+
+    fn stabilizer() -> (Stabilizer<ManualClock>, ManualClock) {
+        let clock = ManualClock::new();
+        (
+            Stabilizer::new(clock.clone(), Stabilization::default()),
+            clock,
+        )
+    }
+
+    /// A capture of `text` selected at `range`, made `secs` into the epoch.
+    fn capture(text: &str, range: Range<usize>, secs: u64) -> Capture {
+        Capture {
+            ranges: vec![range],
+            pid: Some(1),
+            timestamp: UNIX_EPOCH + Duration::from_secs(secs),
+            ..Capture::new(text.to_owned(), Trigger::Poll)
+        }
+    }
+
+    #[test]
+    fn waits_for_the_quiet_period() {
+        let (mut stabilizer, clock) = stabilizer();
+        let hello = capture("hello", 0..5, 1);
+        assert_eq!(stabilizer.observe(Some(hello.clone()), false), None);
+        assert_eq!(
+            stabilizer.deadline(),
+            Some(clock.now() + Duration::from_millis(300))
+        );
+
+        clock.advance(299);
+        assert_eq!(stabilizer.observe(Some(hello.clone()), false), None);
+        clock.advance(1);
+        assert_eq!(
+            stabilizer.observe(Some(hello.clone()), false),
+            Some(Event::SelectionChanged(hello))
+        );
+        assert_eq!(stabilizer.deadline(), None);
+    }
+
+    #[test]
+    fn restarts_the_quiet_period_when_the_selection_changes() {
+        let (mut stabilizer, clock) = stabilizer();
+        stabilizer.observe(Some(capture("hello", 0..5, 1)), false);
+        clock.advance(200);
+        let world = capture("world", 6..11, 2);
+        assert_eq!(stabilizer.observe(Some(world.clone()), false), None);
+        clock.advance(200);
+        assert_eq!(stabilizer.observe(Some(world.clone()), false), None);
+        clock.advance(100);
+        assert_eq!(
+            stabilizer.observe(Some(world.clone()), false),
+            Some(Event::SelectionChanged(world))
+        );
+    }
+
+    #[test]
+    fn waits_for_the_button_to_be_released() {
+        let (mut stabilizer, clock) = stabilizer();
+        let hello = capture("hello", 0..5, 1);
+        stabilizer.observe(Some(hello.clone()), true);
+        clock.advance(1000);
+        assert_eq!(stabilizer.observe(Some(hello.clone()), true), None);
+        assert_eq!(
+            stabilizer.observe(Some(hello.clone()), false),
+            Some(Event::SelectionChanged(hello))
+        );
+    }
+
+    #[test]
+    fn drops_repeats_of_the_last_capture() {
+        let (mut stabilizer, clock) = stabilizer();
+        let hello = capture("hello", 0..5, 1);
+        stabilizer.observe(Some(hello.clone()), false);
+        clock.advance(300);
+        assert!(stabilizer.observe(Some(hello.clone()), false).is_some());
+
+        clock.advance(1000);
+        assert_eq!(stabilizer.observe(Some(hello.clone()), false), None);
+        assert_eq!(stabilizer.deadline(), None);
+    }
+
+    #[test]
+    fn reports_clearing_once() {
+        let (mut stabilizer, clock) = stabilizer();
+        stabilizer.observe(Some(capture("hello", 0..5, 1)), false);
+        clock.advance(300);
+        stabilizer.observe(Some(capture("hello", 0..5, 1)), false);
+
+        assert_eq!(
+            stabilizer.observe(None, false),
+            Some(Event::SelectionCleared)
+        );
+        assert_eq!(stabilizer.observe(None, false), None);
+    }
+
+    #[test]
+    fn merges_a_selection_dragged_out_into_one_capture() {
+        let (mut stabilizer, clock) = stabilizer();
+        stabilizer.observe(Some(capture("hel", 0..3, 1)), true);
+        clock.advance(100);
+        stabilizer.observe(Some(capture("hello", 0..5, 2)), true);
+        clock.advance(100);
+        stabilizer.observe(Some(capture("hello world", 0..11, 3)), false);
+        clock.advance(300);
+
+        // it began when the first part was selected
+        assert_eq!(
+            stabilizer.observe(Some(capture("hello world", 0..11, 3)), false),
+            Some(Event::SelectionChanged(capture("hello world", 0..11, 1)))
+        );
+    }
+
+    #[test]
+    fn marks_extensions_of_the_last_capture() {
+        let (mut stabilizer, clock) = stabilizer();
+        stabilizer.observe(Some(capture("world", 6..11, 1)), false);
+        clock.advance(300);
+        stabilizer.observe(Some(capture("world", 6..11, 1)), false);
+
+        stabilizer.observe(Some(capture("hello world", 0..11, 2)), false);
+        clock.advance(300);
+        let Some(Event::SelectionExtended(extended)) =
+            stabilizer.observe(Some(capture("hello world", 0..11, 2)), false)
+        else {
+            panic!("not an extension");
+        };
+        assert!(extended.extends_previous);
+        assert_eq!(extended.timestamp, UNIX_EPOCH + Duration::from_secs(1));
+    }
+
+    #[test]
+    fn captures_selections_elsewhere_anew() {
+        let (mut stabilizer, clock) = stabilizer();
+        stabilizer.observe(Some(capture("hello", 0..5, 1)), false);
+        clock.advance(300);
+        stabilizer.observe(Some(capture("hello", 0..5, 1)), false);
+
+        // more text, but not around the same range
+        let moved = capture("hello world", 20..31, 2);
+        stabilizer.observe(Some(moved.clone()), false);
+        clock.advance(300);
+        assert_eq!(
+            stabilizer.observe(Some(moved.clone()), false),
+            Some(Event::SelectionChanged(moved))
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::highlight::Token;
+
+/// The system appearance the overlay follows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Appearance {
+    #[default]
+    Light,
+    Dark,
+}
+
+impl From<winit::window::Theme> for Appearance {
+    fn from(theme: winit::window::Theme) -> Self {
+        match theme {
+            winit::window::Theme::Light => Self::Light,
+            winit::window::Theme::Dark => Self::Dark,
+        }
+    }
+}
+
+/// Every field has to be given, since a theme in the config file is merged
+/// into the built-in one it's based on first, see [`Theme::merge`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Theme {
+    #[serde(with = "color")]
+    pub background: glyphon::Color,
+    #[serde(with = "color")]
+    pub foreground: glyphon::Color,
+    #[serde(with = "color")]
+    pub accent: glyphon::Color,
+    #[serde(with = "color")]
+    pub muted: glyphon::Color,
+    #[serde(with = "color")]
+    pub border: glyphon::Color,
+    #[serde(with = "color")]
+    pub shadow: glyphon::Color,
+    pub syntax: SyntaxColors,
+    pub font_family: String,
+    /// Family code selections are shown in.
+    pub code_font_family: String,
+    pub font_size: f32,
+    pub line_height: f32,
+    pub corner_radius: f32,
+    pub padding: f32,
+    pub border_width: f32,
+    pub shadow_radius: f32,
+    /// Blur whatever is behind the panel, tinted by the background color.
+    pub blur: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::light()
+    }
+}
+
+impl Theme {
+    pub fn light() -> Self {
+        Self {
+            background: glyphon::Color::rgba(0xff, 0xff, 0xff, 0xe0),
+            foreground: glyphon::Color::rgb(0x1d, 0x1d, 0x1f),
+            accent: glyphon::Color::rgb(0x00, 0x7a, 0xff),
+            muted: glyphon::Color::rgb(0x6e, 0x6e, 0x73),
+            border: glyphon::Color::rgba(0x00, 0x00, 0x00, 0x1a),
+            shadow: glyphon::Color::rgba(0x00, 0x00, 0x00, 0x40),
+            syntax: SyntaxColors::light(),
+            font_family: "sans-serif".to_owned(),
+            code_font_family: "monospace".to_owned(),
+            font_size: 32.0,
+            line_height: 36.0,
+            corner_radius: 12.0,
+            padding: 12.0,
+            border_width: 1.0,
+            shadow_radius: 12.0,
+            blur: true,
+        }
+    }
+
+    pub fn dark() -> Self {
+        Self {
+            background: glyphon::Color::rgba(0x1e, 0x1e, 0x1e, 0xe0),
+            foreground: glyphon::Color::rgb(0xf5, 0xf5, 0xf7),
+            accent: glyphon::Color::rgb(0x0a, 0x84, 0xff),
+            muted: glyphon::Color::rgb(0x98, 0x98, 0x9d),
+            border: glyphon::Color::rgba(0xff, 0xff, 0xff, 0x26),
+            shadow: glyphon::Color::rgba(0x00, 0x00, 0x00, 0x80),
+            syntax: SyntaxColors::dark(),
+            ..Self::light()
+        }
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "light" => Some(Self::light()),
+            "dark" => Some(Self::dark()),
+            _ => None,
+        }
+    }
+
+    /// The theme with the fields set in `overrides`, a theme in the config file,
+    /// replacing those of this one. Syntax colors are replaced one by one.
+    pub fn merge(&self, overrides: &toml::Table) -> anyhow::Result<Self> {
+        let mut table = toml::Table::try_from(self)?;
+        for (key, value) in overrides {
+            match (table.get_mut(key), value) {
+                (Some(toml::Value::Table(table)), toml::Value::Table(overrides)) => {
+                    table.extend(overrides.clone());
+                }
+                _ => {
+                    table.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        Ok(toml::Value::Table(table).try_into()?)
+    }
+
+    pub fn family(&self) -> glyphon::Family<'_> {
+        family(&self.font_family)
+    }
+
+    pub fn code_family(&self) -> glyphon::Family<'_> {
+        family(&self.code_font_family)
+    }
+
+    /// The color of a highlighted token; plain text and comments use the
+    /// foreground and muted colors.
+    pub fn token_color(&self, token: Token) -> glyphon::Color {
+        match token {
+            Token::Plain => self.foreground,
+            Token::Keyword => self.syntax.keyword,
+            Token::Type => self.syntax.types,
+            Token::String => self.syntax.string,
+            Token::Number => self.syntax.number,
+            Token::Comment => self.muted,
+        }
+    }
+
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    pub fn metrics(&self) -> glyphon::Metrics {
+        glyphon::Metrics::new(self.font_size, self.line_height)
+    }
+
+    /// Logical space between the edge of the window and the panel, leaving
+    /// room for the shadow.
+    pub fn margin(&self) -> f32 {
+        self.shadow_radius
+    }
+
+    /// Logical space between the edge of the window and the text.
+    pub fn inset(&self) -> f32 {
+        self.margin() + self.padding
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SyntaxColors {
+    #[serde(with = "color")]
+    pub keyword: glyphon::Color,
+    #[serde(with = "color")]
+    pub types: glyphon::Color,
+    #[serde(with = "color")]
+    pub string: glyphon::Color,
+    #[serde(with = "color")]
+    pub number: glyphon::Color,
+}
+
+impl Default for SyntaxColors {
+    fn default() -> Self {
+        Self::light()
+    }
+}
+
+impl SyntaxColors {
+    pub fn light() -> Self {
+        Self {
+            keyword: glyphon::Color::rgb(0xad, 0x3d, 0xa4),
+            types: glyphon::Color::rgb(0x3e, 0x80, 0x87),
+            string: glyphon::Color::rgb(0xd1, 0x2f, 0x1b),
+            number: glyphon::Color::rgb(0x27, 0x2a, 0xd8),
+        }
+    }
+
+    pub fn dark() -> Self {
+        Self {
+            keyword: glyphon::Color::rgb(0xfc, 0x5f, 0xa3),
+            types: glyphon::Color::rgb(0x5d, 0xd8, 0xff),
+            string: glyphon::Color::rgb(0xfc, 0x6a, 0x5d),
+            number: glyphon::Color::rgb(0xd0, 0xbf, 0x69),
+        }
+    }
+}
+
+/// Maps the generic CSS family names, treating anything else as a font family
+/// name.
+fn family(name: &str) -> glyphon::Family<'_> {
+    match name {
+        "serif" => glyphon::Family::Serif,
+        "sans-serif" => glyphon::Family::SansSerif,
+        "cursive" => glyphon::Family::Cursive,
+        "fantasy" => glyphon::Family::Fantasy,
+        "monospace" => glyphon::Family::Monospace,
+        name => glyphon::Family::Name(name),
+    }
+}
+
+/// Parses `#rgb`, `#rrggbb` or `#rrggbbaa`.
+pub fn parse_color(hex: &str) -> Option<glyphon::Color> {
+    let hex = hex.strip_prefix('#')?;
+    // from_str_radix also takes a leading sign, like `+f`
+    if !matches!(hex.len(), 3 | 6 | 8) || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    if hex.len() == 3 {
+        let channel = |i: usize| Some(u8::from_str_radix(hex.get(i..i + 1)?, 16).ok()? * 0x11);
+        return Some(glyphon::Color::rgb(channel(0)?, channel(1)?, channel(2)?));
+    }
+
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    let alpha = if hex.len() == 8 { channel(6)? } else { 0xff };
+    Some(glyphon::Color::rgba(
+        channel(0)?,
+        channel(2)?,
+        channel(4)?,
+        alpha,
+    ))
+}
+
+/// Colors as `#rrggbbaa` in the config file.
+mod color {
+    use super::*;
+
+    pub fn serialize<S>(color: &glyphon::Color, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let hex = format!(
+            "#{:02x}{:02x}{:02x}{:02x}",
+            color.r(),
+            color.g(),
+            color.b(),
+            color.a()
+        );
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<glyphon::Color, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let hex = String::deserialize(deserializer)?;
+        parse_color(&hex).ok_or_else(|| {
+            serde::de::Error::custom(format!("invalid color {hex:?}, expected #rrggbb"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_nothing_into_an_equal_theme() {
+        for theme in [Theme::light(), Theme::dark()] {
+            assert_eq!(theme.merge(&toml::Table::new()).unwrap(), theme);
+        }
+    }
+
+    #[test]
+    fn rejects_themes_missing_fields() {
+        assert!(toml::from_str::<Theme>("accent = \"#ff9500\"").is_err());
+    }
+}